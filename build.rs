@@ -0,0 +1,27 @@
+// with the `shader-check` feature, shader errors fail the build instead of the first run
+#[cfg(feature = "shader-check")]
+#[path = "src/shader_loader.rs"]
+#[allow(dead_code)]
+mod shader_loader;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/shader");
+
+    #[cfg(feature = "shader-check")]
+    {
+        let mut failed = false;
+        for file in shader_loader::SHADER_FILES {
+            if let Err(e) = file.compile() {
+                for line in e.to_string().lines() {
+                    println!("cargo:warning={line}");
+                }
+                failed = true;
+            }
+        }
+
+        if failed {
+            std::process::exit(1);
+        }
+    }
+}
@@ -1 +0,0 @@
-mangohud ./target/release/vereon
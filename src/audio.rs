@@ -1,41 +1,664 @@
-use cpal::traits::{DeviceTrait, HostTrait};
-use log::{error, info};
+//! Microphone or line input captured with cpal. Samples are mixed down to mono on the audio
+//! thread and handed to the renderer through a ring buffer.
+
+use crate::beat::BeatDetector;
+use crate::playback::Playback;
+use crate::ring_buffer::{ring_buffer, Consumer, Producer};
+use crate::spectrum::Analyzer;
+use crate::uniform::Uniform;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    BufferSize, Device, FromSample, Host, SampleFormat, SizedSample, Stream, StreamConfig,
+    StreamError, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+};
+use log::{debug, error, info, warn};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use toml::{Table, Value};
+
+/// About a second of mono samples at the usual rates, read every frame.
+pub const RING_CAPACITY: usize = 1 << 16;
+/// How often a lost or missing device is looked for again.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Offered when a device supports a range of rates.
+const COMMON_SAMPLE_RATES: [u32; 8] = [22_050, 32_000, 44_100, 48_000, 88_200, 96_000, 176_400, 192_000];
+/// Offered when a device supports a range of buffer sizes, in frames.
+const COMMON_BUFFER_SIZES: [u32; 8] = [64, 128, 256, 512, 1024, 2048, 4096, 8192];
+
+/// What to open, `None` picks the default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AudioSelection {
+    pub host: Option<String>,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    /// frames per callback
+    pub buffer_size: Option<u32>,
+}
+
+impl AudioSelection {
+    /// Reads the `[audio]` table, missing keys keep the default.
+    pub fn from_document(document: &Table) -> Result<Self, AudioError> {
+        let mut selection = Self::default();
+        let Some(table) = document.get("audio").and_then(Value::as_table) else {
+            return Ok(selection);
+        };
+
+        for (key, value) in table {
+            let invalid = || AudioError(format!("audio.{key} can't be {} {value}.", value.type_str()));
+            let positive = |value: &Value| match value {
+                Value::Integer(i) if *i > 0 => u32::try_from(*i).ok(),
+                _ => None,
+            };
+            match (key.as_str(), value) {
+                ("host", Value::String(s)) => selection.host = Some(s.clone()),
+                ("device", Value::String(s)) => selection.device = Some(s.clone()),
+                ("sample_rate", v) => selection.sample_rate = Some(positive(v).ok_or_else(invalid)?),
+                ("channels", v) => {
+                    let channels = positive(v).and_then(|c| u16::try_from(c).ok());
+                    selection.channels = Some(channels.ok_or_else(invalid)?);
+                }
+                ("buffer_size", v) => selection.buffer_size = Some(positive(v).ok_or_else(invalid)?),
+                ("host" | "device", _) => return Err(invalid()),
+                _ => return Err(AudioError(format!("unknown key audio.{key}."))),
+            }
+        }
+
+        Ok(selection)
+    }
+
+    /// Sets the `[audio]` table to this selection, leaving out what picks the default.
+    pub fn write_document(&self, document: &mut Table) {
+        let mut table = Table::new();
+        if let Some(host) = &self.host {
+            table.insert("host".into(), Value::String(host.clone()));
+        }
+        if let Some(device) = &self.device {
+            table.insert("device".into(), Value::String(device.clone()));
+        }
+        let numbers = [
+            ("sample_rate", self.sample_rate),
+            ("channels", self.channels.map(u32::from)),
+            ("buffer_size", self.buffer_size),
+        ];
+        for (key, number) in numbers {
+            if let Some(number) = number {
+                table.insert(key.into(), Value::Integer(number as i64));
+            }
+        }
+        document.insert("audio".into(), Value::Table(table));
+    }
+}
+
+/// An input device and what it can capture.
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<SupportedStreamConfigRange>,
+}
+
+/// A host and its input devices.
+#[derive(Clone, Debug)]
+pub struct HostInfo {
+    pub name: String,
+    pub is_default: bool,
+    pub devices: Vec<DeviceInfo>,
+}
+
+/// Every available host with its input devices, hosts and devices that fail to open are skipped.
+pub fn list_devices() -> Vec<HostInfo> {
+    let default_host = cpal::default_host().id();
+
+    cpal::available_hosts()
+        .into_iter()
+        .filter_map(|host_id| {
+            let host = cpal::host_from_id(host_id).ok()?;
+            let default_device = host.default_input_device().and_then(|d| d.name().ok());
+            let devices = host
+                .input_devices()
+                .ok()?
+                .filter_map(|device| {
+                    let name = device.name().ok()?;
+                    let configs = device.supported_input_configs().map(Iterator::collect).unwrap_or_default();
+                    Some(DeviceInfo {
+                        is_default: default_device.as_ref() == Some(&name),
+                        name,
+                        configs,
+                    })
+                })
+                .collect();
+
+            Some(HostInfo {
+                name: host_id.name().to_string(),
+                is_default: host_id == default_host,
+                devices,
+            })
+        })
+        .collect()
+}
+
+/// Rates to offer for `configs`, the common ones inside a range and the ends of every range.
+pub fn sample_rates(configs: &[SupportedStreamConfigRange]) -> Vec<u32> {
+    let mut rates: Vec<u32> = configs
+        .iter()
+        .flat_map(|config| {
+            let (min, max) = (config.min_sample_rate().0, config.max_sample_rate().0);
+            COMMON_SAMPLE_RATES
+                .into_iter()
+                .filter(move |rate| (min..=max).contains(rate))
+                .chain([min, max])
+        })
+        .collect();
+    rates.sort_unstable();
+    rates.dedup();
+
+    rates
+}
+
+pub fn channel_counts(configs: &[SupportedStreamConfigRange]) -> Vec<u16> {
+    let mut channels: Vec<u16> = configs.iter().map(|config| config.channels()).collect();
+    channels.sort_unstable();
+    channels.dedup();
+
+    channels
+}
+
+/// Buffer sizes to offer for `configs`, the common ones that fit any of them.
+pub fn buffer_sizes(configs: &[SupportedStreamConfigRange]) -> Vec<u32> {
+    COMMON_BUFFER_SIZES
+        .into_iter()
+        .filter(|&frames| configs.iter().any(|config| buffer_fits(config.buffer_size(), frames)))
+        .collect()
+}
+
+/// The stream that is actually running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub host: String,
+    pub device: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+    pub buffer_size: Option<u32>,
+}
+
+impl fmt::Display for StreamInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} on {}, {} Hz, {} channels, {}",
+            self.device, self.host, self.sample_rate, self.channels, self.sample_format
+        )?;
+        match self.buffer_size {
+            Some(frames) => write!(f, ", {frames} frames"),
+            None => write!(f, ", default buffer"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioError(pub String);
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AudioError {}
 
 pub struct Audio {
+    selection: AudioSelection,
+    stream: Option<Stream>,
+    info: Option<StreamInfo>,
+    samples: Consumer,
+    // set from the audio thread when the device goes away
+    lost: Arc<AtomicBool>,
+    last_attempt: Instant,
+    last_error: Option<AudioError>,
+    devices: Vec<HostInfo>,
+}
+
+fn log_devices(hosts: &[HostInfo]) {
+    info!("supported hosts: {:?}", cpal::ALL_HOSTS);
+    info!("available hosts: {:?}", hosts.iter().map(|host| &host.name).collect::<Vec<_>>());
+
+    for host in hosts {
+        let default_in = host.devices.iter().find(|device| device.is_default).map(|device| &device.name);
+        info!("[{}] default input device: {:?}", host.name, default_in);
+
+        for (i, device) in host.devices.iter().enumerate() {
+            info!("[{}] device {}: {:?}", host.name, i, device.name);
+            device.configs.iter().for_each(|conf| debug!("      input stream config: {:?}", conf));
+        }
+    }
+}
+
+fn find_host(name: Option<&str>) -> Result<Host, AudioError> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| AudioError(format!("audio host {name} isn't available.")))?;
+
+    cpal::host_from_id(id).map_err(|e| AudioError(format!("audio host {name}: {e}")))
+}
+
+fn find_device(host: &Host, name: Option<&str>) -> Result<Device, AudioError> {
+    let host_name = host.id().name();
+    let Some(name) = name else {
+        return host
+            .default_input_device()
+            .ok_or_else(|| AudioError(format!("{host_name} has no default input device.")));
+    };
 
+    host.input_devices()
+        .map_err(|e| AudioError(format!("listing {host_name} input devices: {e}")))?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| AudioError(format!("input device {name} not found on {host_name}.")))
+}
+
+fn buffer_fits(buffer_size: &SupportedBufferSize, frames: u32) -> bool {
+    match buffer_size {
+        SupportedBufferSize::Range { min, max } => (*min..=*max).contains(&frames),
+        SupportedBufferSize::Unknown => true,
+    }
+}
+
+// formats we can turn into f32, preferring the ones that need no conversion
+fn format_rank(format: SampleFormat) -> Option<u8> {
+    match format {
+        SampleFormat::F32 => Some(0),
+        SampleFormat::I16 | SampleFormat::I32 | SampleFormat::U16 | SampleFormat::U8 => Some(1),
+        _ => None,
+    }
+}
+
+/// The first supported range matching every part of `selection` that is set.
+pub fn choose_config(
+    ranges: &[SupportedStreamConfigRange],
+    selection: &AudioSelection,
+) -> Option<SupportedStreamConfig> {
+    ranges
+        .iter()
+        .filter(|range| format_rank(range.sample_format()).is_some())
+        .filter(|range| selection.channels.is_none_or(|c| c == range.channels()))
+        .filter(|range| selection.buffer_size.is_none_or(|b| buffer_fits(range.buffer_size(), b)))
+        .filter_map(|&range| match selection.sample_rate {
+            Some(rate) => range.try_with_sample_rate(cpal::SampleRate(rate)),
+            None => Some(range.with_max_sample_rate()),
+        })
+        .min_by_key(|config| format_rank(config.sample_format()))
+}
+
+/// Mean of the channels of every frame in `data`.
+pub fn push_mono<T>(data: &[T], channels: usize, producer: &mut Producer)
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    for frame in data.chunks_exact(channels) {
+        let sum: f32 = frame.iter().map(|&s| s.to_sample::<f32>()).sum();
+        producer.push(sum / channels as f32);
+    }
+}
+
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut producer: Producer,
+    lost: Arc<AtomicBool>,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| push_mono(data, channels, &mut producer),
+        move |e| match e {
+            StreamError::DeviceNotAvailable => lost.store(true, Ordering::Relaxed),
+            e => error!("audio stream: {}", e),
+        },
+        None,
+    )
 }
 
 impl Audio {
-    pub fn new() -> Self {
-        info!("supported hosts: {:?}", cpal::ALL_HOSTS);
-        let available_hosts = cpal::available_hosts();
-        info!("available hosts: {:?}", available_hosts);
+    /// Never fails, without a usable device it keeps trying to connect in `update`.
+    pub fn new(selection: AudioSelection) -> Self {
+        let devices = list_devices();
+        log_devices(&devices);
 
-        for host_id in available_hosts {
-            let host = cpal::host_from_id(host_id).unwrap();
+        let mut audio = Self {
+            selection,
+            stream: None,
+            info: None,
+            samples: ring_buffer(RING_CAPACITY).1,
+            lost: Arc::new(AtomicBool::new(false)),
+            last_attempt: Instant::now(),
+            last_error: None,
+            devices,
+        };
+        audio.reconnect();
+
+        audio
+    }
 
-            let default_in = host.default_input_device().map(|e| e.name().unwrap());
-            info!("[{}] default input device: {:?}", host_id.name(), default_in);
+    fn connect(&mut self) -> Result<(), AudioError> {
+        let host = find_host(self.selection.host.as_deref())?;
+        let device = find_device(&host, self.selection.device.as_deref())?;
+        let device_name = device.name().unwrap_or_default();
 
-            let devices = host.devices().unwrap();
-            for (i, device) in devices.enumerate() {
-                info!("[{}] device {}: {:?}", host_id.name(), i, device.name().unwrap());
+        let supported = match self.selection {
+            AudioSelection {
+                sample_rate: None,
+                channels: None,
+                buffer_size: None,
+                ..
+            } => device.default_input_config().ok(),
+            _ => None,
+        };
+        let supported = match supported {
+            Some(supported) => supported,
+            None => {
+                let ranges: Vec<_> = device
+                    .supported_input_configs()
+                    .map_err(|e| AudioError(format!("{device_name}: {e}")))?
+                    .collect();
+                choose_config(&ranges, &self.selection).ok_or_else(|| {
+                    AudioError(format!("{device_name} supports no input config matching {:?}.", self.selection))
+                })?
+            }
+        };
+
+        let mut config = supported.config();
+        config.buffer_size = match self.selection.buffer_size {
+            Some(frames) => BufferSize::Fixed(frames),
+            None => BufferSize::Default,
+        };
+
+        let (producer, samples) = ring_buffer(RING_CAPACITY);
+        let lost = Arc::new(AtomicBool::new(false));
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, producer, lost.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, producer, lost.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, producer, lost.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, producer, lost.clone()),
+            SampleFormat::U8 => build_stream::<u8>(&device, &config, producer, lost.clone()),
+            format => return Err(AudioError(format!("{device_name}: unsupported sample format {format}."))),
+        }
+        .map_err(|e| AudioError(format!("{device_name}: {e}")))?;
+        stream.play().map_err(|e| AudioError(format!("{device_name}: {e}")))?;
 
-                if let Ok(conf) = device.default_input_config() {
-                    println!("      default input stream config: {:?}", conf);
+        let info = StreamInfo {
+            host: host.id().name().to_string(),
+            device: device_name,
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            sample_format: supported.sample_format(),
+            buffer_size: self.selection.buffer_size,
+        };
+        info!("capturing audio from {}.", info);
+
+        self.stream = Some(stream);
+        self.info = Some(info);
+        self.samples = samples;
+        self.lost = lost;
+
+        Ok(())
+    }
+
+    fn reconnect(&mut self) {
+        self.stream = None;
+        self.info = None;
+        self.last_attempt = Instant::now();
+
+        match self.connect() {
+            Ok(()) => self.last_error = None,
+            Err(e) => {
+                // retries usually fail the same way, only say so once
+                if self.last_error.as_ref() != Some(&e) {
+                    warn!("no audio input: {}", e);
                 }
-                let input_configs = match device.supported_input_configs() {
-                    Ok(f) => f.collect(),
-                    Err(e) => {
-                        error!("      error getting supported input configs: {:?}", e);
-                        Vec::new()
-                    }
-                };
+                self.last_error = Some(e);
             }
         }
+    }
 
-        Self {
+    /// Opens `selection` instead of the current stream.
+    pub fn select(&mut self, selection: AudioSelection) {
+        self.selection = selection;
+        self.last_error = None;
+        self.reconnect();
+    }
+
+    pub fn selection(&self) -> &AudioSelection {
+        &self.selection
+    }
+
+    /// `None` while there's no stream.
+    pub fn info(&self) -> Option<&StreamInfo> {
+        self.info.as_ref()
+    }
+
+    /// Hosts and devices as of the start or the last `refresh_devices`.
+    pub fn devices(&self) -> &[HostInfo] {
+        &self.devices
+    }
+
+    /// Lists the devices again, e.g. after plugging one in.
+    pub fn refresh_devices(&mut self) {
+        self.devices = list_devices();
+    }
+
+    /// Why the last attempt to open a stream failed.
+    pub fn error(&self) -> Option<&AudioError> {
+        self.last_error.as_ref()
+    }
+
+    /// Reconnects after the device was lost or when there was none, call once per frame.
+    pub fn update(&mut self) {
+        if self.lost.load(Ordering::Relaxed) && self.stream.is_some() {
+            error!("audio input device lost, reconnecting.");
+            self.stream = None;
+            self.info = None;
+        }
+
+        if self.stream.is_none() && self.last_attempt.elapsed() >= RECONNECT_INTERVAL {
+            self.reconnect();
+        }
+    }
+
+    /// Moves the oldest captured mono samples into `out`, returns how many there were.
+    pub fn read(&mut self, out: &mut [f32]) -> usize {
+        self.samples.pop_slice(out)
+    }
+
+    /// Samples waiting to be read.
+    pub fn available(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new(AudioSelection::default())
+    }
+}
+
+/// Where the analyzers get their samples from.
+pub enum AudioSource {
+    Input(Audio),
+    File(Playback),
+}
+
+impl AudioSource {
+    /// Call once per frame, `time` is the current `uniform.time`.
+    pub fn update(&mut self, time: u32) {
+        match self {
+            AudioSource::Input(audio) => audio.update(),
+            AudioSource::File(playback) => playback.sync(time),
+        }
+    }
+
+    /// New samples come in every frame, from a running input stream or a playing file.
+    pub fn is_live(&self) -> bool {
+        match self {
+            AudioSource::Input(audio) => audio.info().is_some(),
+            AudioSource::File(playback) => playback.is_playing(),
+        }
+    }
+
+    /// `None` while there's no input stream.
+    pub fn sample_rate(&self) -> Option<u32> {
+        match self {
+            AudioSource::Input(audio) => audio.info().map(|info| info.sample_rate),
+            AudioSource::File(playback) => Some(playback.clip().sample_rate),
+        }
+    }
+
+    /// Mono samples since the last call, see `Audio::read` and `Playback::read`.
+    pub fn read(&mut self, out: &mut [f32]) -> usize {
+        match self {
+            AudioSource::Input(audio) => audio.read(out),
+            AudioSource::File(playback) => playback.read(out),
+        }
+    }
+}
+
+/// Spectrum and beats of an `AudioSource`, updated once per frame.
+pub struct AudioAnalysis {
+    pub analyzer: Analyzer,
+    pub beats: BeatDetector,
+    samples: Vec<f32>,
+}
+
+impl AudioAnalysis {
+    /// Takes in what `source` captured or played since the last frame, `dt` seconds ago.
+    pub fn update(&mut self, source: &mut AudioSource, dt: f32) {
+        match source.sample_rate() {
+            Some(sample_rate) => {
+                let count = source.read(&mut self.samples);
+                self.analyzer.push(&self.samples[..count]);
+                self.analyzer.analyze(sample_rate, dt);
+                self.beats.push(&self.samples[..count], sample_rate);
+            }
+            None => {
+                self.analyzer.clear();
+                self.beats.reset();
+            }
+        }
+    }
+
+    /// See `BeatDetector::write_uniform`.
+    pub fn write_uniform(&self, uniform: &mut Uniform) {
+        self.beats.write_uniform(uniform);
+    }
+
+    /// For `Scene::write_audio`.
+    pub fn texels(&self) -> Vec<u8> {
+        self.analyzer.texels()
+    }
+}
 
+impl Default for AudioAnalysis {
+    fn default() -> Self {
+        Self {
+            analyzer: Analyzer::default(),
+            beats: BeatDetector::default(),
+            samples: vec![0.0; RING_CAPACITY],
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpal::SampleRate;
+
+    #[test]
+    fn mixes_down_to_mono() {
+        let (mut producer, mut consumer) = ring_buffer(8);
+        push_mono(&[1.0f32, 0.0, -0.5, -0.5, 0.25], 2, &mut producer);
+        push_mono(&[i16::MIN, 0], 1, &mut producer);
+
+        let mut out = [0.0; 8];
+        assert_eq!(consumer.pop_slice(&mut out), 4);
+        assert_eq!(out[..4], [0.5, -0.5, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn chooses_matching_config() {
+        let range = |channels, format| {
+            SupportedStreamConfigRange::new(
+                channels,
+                SampleRate(8_000),
+                SampleRate(48_000),
+                SupportedBufferSize::Range { min: 64, max: 1024 },
+                format,
+            )
+        };
+        let ranges = [range(1, SampleFormat::I16), range(2, SampleFormat::F32), range(2, SampleFormat::F64)];
+
+        let config = choose_config(&ranges, &AudioSelection::default()).unwrap();
+        assert_eq!((config.channels(), config.sample_format()), (2, SampleFormat::F32));
+        assert_eq!(config.sample_rate(), SampleRate(48_000));
+
+        let selection = AudioSelection { sample_rate: Some(44_100), channels: Some(1), ..Default::default() };
+        let config = choose_config(&ranges, &selection).unwrap();
+        assert_eq!((config.channels(), config.sample_format()), (1, SampleFormat::I16));
+        assert_eq!(config.sample_rate(), SampleRate(44_100));
+
+        assert!(choose_config(&ranges, &AudioSelection { sample_rate: Some(96_000), ..Default::default() }).is_none());
+        assert!(choose_config(&ranges, &AudioSelection { buffer_size: Some(4096), ..Default::default() }).is_none());
+    }
+
+    #[test]
+    fn offers_options_from_configs() {
+        let configs = [
+            SupportedStreamConfigRange::new(
+                2,
+                SampleRate(44_100),
+                SampleRate(48_000),
+                SupportedBufferSize::Range { min: 100, max: 600 },
+                SampleFormat::F32,
+            ),
+            SupportedStreamConfigRange::new(1, SampleRate(11_025), SampleRate(11_025), SupportedBufferSize::Range { min: 2048, max: 2048 }, SampleFormat::I16),
+        ];
+
+        assert_eq!(sample_rates(&configs), [11_025, 44_100, 48_000]);
+        assert_eq!(channel_counts(&configs), [1, 2]);
+        assert_eq!(buffer_sizes(&configs), [128, 256, 512, 2048]);
+    }
+
+    #[test]
+    fn selection_round_trips() {
+        let selection = AudioSelection {
+            host: Some("ALSA".into()),
+            device: Some("hw:1,0".into()),
+            sample_rate: Some(48_000),
+            channels: None,
+            buffer_size: Some(256),
+        };
+        let mut document = Table::new();
+        selection.write_document(&mut document);
+        let document = document.to_string().parse().unwrap();
+        assert_eq!(AudioSelection::from_document(&document).unwrap(), selection);
+
+        let error = |source: &str| AudioSelection::from_document(&source.parse().unwrap()).unwrap_err().0;
+        assert!(error("[audio]\nsample_rate = -1").contains("audio.sample_rate"));
+        assert!(error("[audio]\nchannels = 70000").contains("audio.channels"));
+        assert!(error("[audio]\nhost = 1").contains("audio.host"));
+        assert!(error("[audio]\nrate = 1").contains("unknown key"));
+        assert_eq!(AudioSelection::from_document(&Table::new()).unwrap(), AudioSelection::default());
+    }
+}
@@ -0,0 +1,226 @@
+//! Decodes audio files for playback. Only wav is handled here, flac and ogg are recognized
+//! so they can be rejected with a hint instead of a parse error.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// A whole decoded file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// interleaved, in [-1, 1]
+    pub samples: Vec<f32>,
+    /// the channels mixed down, what the analyzers get
+    pub mono: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioFileError(pub String);
+
+impl fmt::Display for AudioFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AudioFileError {}
+
+fn error(message: impl Into<String>) -> AudioFileError {
+    AudioFileError(message.into())
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn decode_sample(format: u16, bits: u16, bytes: &[u8]) -> f32 {
+    match (format, bits) {
+        (FORMAT_PCM, 8) => (bytes[0] as f32 - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32_768.0,
+        // sign extended through the top byte of an i32
+        (FORMAT_PCM, 24) => i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0,
+        (FORMAT_PCM, 32) => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2_147_483_648.0,
+        (FORMAT_FLOAT, 32) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        (FORMAT_FLOAT, 64) => f64::from_le_bytes(bytes[..8].try_into().expect("64 bit samples have 8 bytes.")) as f32,
+        _ => unreachable!("the format is checked before decoding."),
+    }
+}
+
+impl Clip {
+    /// Builds a clip from interleaved samples.
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Self {
+        let mono = samples
+            .chunks_exact(channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        Self { sample_rate, channels, samples, mono }
+    }
+
+    /// Frames, so samples per channel.
+    pub fn len(&self) -> usize {
+        self.mono.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mono.is_empty()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.len() as f64 / self.sample_rate as f64
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AudioFileError> {
+        let bytes = fs::read(path).map_err(|e| error(format!("{}: {e}", path.display())))?;
+        Self::decode(&bytes).map_err(|e| error(format!("{}: {e}", path.display())))
+    }
+
+    /// Decodes a wav file, flac and ogg are reported as such.
+    pub fn decode(bytes: &[u8]) -> Result<Self, AudioFileError> {
+        match bytes.get(..4) {
+            Some(b"fLaC") | Some(b"OggS") => {
+                return Err(error("only wav can be decoded, convert it first, e.g. with `ffmpeg -i <file> out.wav`."))
+            }
+            Some(b"RIFF") if bytes.get(8..12) == Some(b"WAVE") => {}
+            _ => return Err(error("not a wav file.")),
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut at = 12;
+        while at + 8 <= bytes.len() {
+            let id = &bytes[at..at + 4];
+            // streamed files may leave the size open, the data runs to the end then
+            let size = (u32_at(bytes, at + 4) as usize).min(bytes.len() - at - 8);
+            let body = &bytes[at + 8..at + 8 + size];
+
+            match id {
+                b"fmt " if size >= 16 => format = Some(body),
+                b"fmt " => return Err(error("fmt chunk is too short.")),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+            // chunks are padded to an even size
+            at += 8 + size + size % 2;
+        }
+
+        let format = format.ok_or_else(|| error("no fmt chunk."))?;
+        let data = data.ok_or_else(|| error("no data chunk."))?;
+
+        let mut tag = u16_at(format, 0);
+        let channels = u16_at(format, 2);
+        let sample_rate = u32_at(format, 4);
+        let bits = u16_at(format, 14);
+        if tag == FORMAT_EXTENSIBLE {
+            if format.len() < 26 {
+                return Err(error("extensible fmt chunk is too short."));
+            }
+            // the first two bytes of the sub format guid are the actual format
+            tag = u16_at(format, 24);
+        }
+
+        if !matches!((tag, bits), (FORMAT_PCM, 8 | 16 | 24 | 32) | (FORMAT_FLOAT, 32 | 64)) {
+            return Err(error(format!("unsupported sample format {tag} with {bits} bits.")));
+        }
+        if channels == 0 || sample_rate == 0 {
+            return Err(error("no channels or a sample rate of zero."));
+        }
+
+        let bytes_per_sample = bits as usize / 8;
+        let frame_size = bytes_per_sample * channels as usize;
+        let samples = data[..data.len() - data.len() % frame_size]
+            .chunks_exact(bytes_per_sample)
+            .map(|sample| decode_sample(tag, bits, sample))
+            .collect();
+
+        Ok(Self::new(sample_rate, channels, samples))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wav file with `data` as the samples, `extensible` uses the longer fmt chunk.
+    fn wav(tag: u16, channels: u16, sample_rate: u32, bits: u16, data: &[u8], extensible: bool) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&(if extensible { FORMAT_EXTENSIBLE } else { tag }).to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        let block_align = channels * bits / 8;
+        fmt.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&bits.to_le_bytes());
+        if extensible {
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&bits.to_le_bytes());
+            fmt.extend_from_slice(&0u32.to_le_bytes());
+            fmt.extend_from_slice(&tag.to_le_bytes());
+            fmt.extend_from_slice(&[0; 14]);
+        }
+
+        let mut chunks = Vec::new();
+        for (id, body) in [(b"fmt ", &fmt[..]), (b"LIST", &[1, 2, 3][..]), (b"data", data)] {
+            chunks.extend_from_slice(id);
+            chunks.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            chunks.extend_from_slice(body);
+            if body.len() % 2 == 1 {
+                chunks.push(0);
+            }
+        }
+
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+        file.extend_from_slice(b"WAVE");
+        file.extend_from_slice(&chunks);
+        file
+    }
+
+    #[test]
+    fn decodes_integer_pcm() {
+        let data: Vec<u8> = [i16::MAX, i16::MIN, 0, 16_384].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let clip = Clip::decode(&wav(FORMAT_PCM, 2, 44_100, 16, &data, false)).unwrap();
+        assert_eq!((clip.sample_rate, clip.channels, clip.len()), (44_100, 2, 2));
+        assert_eq!(clip.samples, [32_767.0 / 32_768.0, -1.0, 0.0, 0.5]);
+        assert_eq!(clip.mono[1], 0.25);
+
+        let clip = Clip::decode(&wav(FORMAT_PCM, 1, 8_000, 24, &[0, 0, 0x80, 0, 0, 0x40], false)).unwrap();
+        assert_eq!(clip.samples, [-1.0, 0.5]);
+
+        let clip = Clip::decode(&wav(FORMAT_PCM, 1, 8_000, 8, &[0, 128, 192], true)).unwrap();
+        assert_eq!(clip.samples, [-1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn decodes_float() {
+        let data: Vec<u8> = [0.25f32, -0.75].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let clip = Clip::decode(&wav(FORMAT_FLOAT, 1, 48_000, 32, &data, true)).unwrap();
+        assert_eq!(clip.samples, [0.25, -0.75]);
+        assert_eq!(clip.duration(), 2.0 / 48_000.0);
+
+        let data: Vec<u8> = [0.5f64].iter().flat_map(|s| s.to_le_bytes()).collect();
+        assert_eq!(Clip::decode(&wav(FORMAT_FLOAT, 1, 48_000, 64, &data, false)).unwrap().samples, [0.5]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let error = |bytes: &[u8]| Clip::decode(bytes).unwrap_err().0;
+
+        assert!(error(b"fLaC\0\0\0\x22").contains("only wav"));
+        assert!(error(b"OggS\0\x02").contains("only wav"));
+        assert!(error(b"ID3\x04").contains("not a wav"));
+        assert!(error(&wav(2, 1, 8_000, 4, &[0; 4], false)).contains("unsupported sample format 2"));
+        assert!(error(&wav(FORMAT_PCM, 0, 8_000, 16, &[0; 4], false)).contains("no channels"));
+        assert!(error(&b"RIFF\x04\0\0\0WAVE"[..]).contains("no fmt"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Onsets from spectral flux against an adaptive threshold, a tempo estimate from the time
+//! between them and the energy in three bands. Works on fixed hops of samples, so the results
+//! don't depend on the frame rate.
+
+use crate::spectrum::fft;
+use crate::uniform::Uniform;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+pub const FRAME_SIZE: usize = 1024;
+pub const HOP_SIZE: usize = 512;
+
+/// Upper ends of the bass and mid bands in Hz, treble goes up to half the sample rate.
+pub const BASS_MAX: f32 = 250.0;
+pub const MID_MAX: f32 = 4_000.0;
+
+/// Seconds of flux the threshold is the mean of.
+const THRESHOLD_WINDOW: f32 = 0.5;
+/// How far above the mean the flux has to go.
+const THRESHOLD_FACTOR: f32 = 1.5;
+/// Keeps noise in near silence from counting as onsets.
+const THRESHOLD_MIN: f32 = 0.01;
+/// Onsets closer together than this are one onset.
+const MIN_ONSET_INTERVAL: f32 = 0.1;
+/// Seconds for `beat` to fall to 1/e after an onset.
+const BEAT_DECAY: f32 = 0.1;
+
+/// Intervals are folded into this range, so double or half time reads the same.
+pub const MIN_BPM: f32 = 70.0;
+pub const MAX_BPM: f32 = 180.0;
+/// Onsets the tempo is estimated from.
+const ONSET_HISTORY: usize = 17;
+/// Intervals needed before there's a tempo.
+const MIN_INTERVALS: usize = 4;
+
+pub struct BeatDetector {
+    sample_rate: u32,
+    // samples waiting for a full frame, the oldest first
+    pending: Vec<f32>,
+    window: Vec<f32>,
+    window_sum: f32,
+    // turns the summed squares of a band into the amplitude of a sine with that power
+    power_scale: f32,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    last_magnitudes: Vec<f32>,
+    flux_history: VecDeque<f32>,
+    // seconds of audio analyzed since the last reset
+    time: f32,
+    onsets: VecDeque<f32>,
+    beat: f32,
+    bpm: f32,
+    bands: [f32; 3],
+}
+
+/// Tempo from the typical interval between `onsets` after folding them into `MIN_BPM` to `MAX_BPM`,
+/// zero until there are enough of them.
+pub fn estimate_bpm(onsets: &[f32]) -> f32 {
+    let mut intervals: Vec<f32> = onsets
+        .windows(2)
+        .map(|pair| {
+            let mut interval = pair[1] - pair[0];
+            while interval < 60.0 / MAX_BPM {
+                interval *= 2.0;
+            }
+            while interval > 60.0 / MIN_BPM {
+                interval /= 2.0;
+            }
+            interval
+        })
+        .collect();
+    if intervals.len() < MIN_INTERVALS {
+        return 0.0;
+    }
+
+    intervals.sort_by(f32::total_cmp);
+    let median = intervals[intervals.len() / 2];
+    // onsets snap to hops, the mean around the median evens that out
+    let close: Vec<f32> = intervals
+        .into_iter()
+        .filter(|interval| (interval - median).abs() < 0.1 * median)
+        .collect();
+
+    60.0 * close.len() as f32 / close.iter().sum::<f32>()
+}
+
+impl BeatDetector {
+    pub fn new() -> Self {
+        let window: Vec<f32> = (0..FRAME_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_SIZE as f32).cos())
+            .collect();
+
+        let window_sum: f32 = window.iter().sum();
+        let window_power: f32 = window.iter().map(|w| w * w).sum();
+
+        Self {
+            sample_rate: 0,
+            pending: Vec::with_capacity(2 * FRAME_SIZE),
+            window_sum,
+            power_scale: window_sum * window_sum / (FRAME_SIZE as f32 * window_power),
+            window,
+            re: vec![0.0; FRAME_SIZE],
+            im: vec![0.0; FRAME_SIZE],
+            last_magnitudes: vec![0.0; FRAME_SIZE / 2 + 1],
+            flux_history: VecDeque::new(),
+            time: 0.0,
+            onsets: VecDeque::new(),
+            beat: 0.0,
+            bpm: 0.0,
+            bands: [0.0; 3],
+        }
+    }
+
+    /// Forgets everything heard so far.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Analyzes mono samples, a new `sample_rate` starts over.
+    pub fn push(&mut self, samples: &[f32], sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.reset();
+            self.sample_rate = sample_rate;
+        }
+
+        for chunk in samples.chunks(HOP_SIZE) {
+            self.pending.extend_from_slice(chunk);
+            while self.pending.len() >= FRAME_SIZE {
+                self.process_frame();
+                self.pending.drain(..HOP_SIZE);
+            }
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let hop_seconds = HOP_SIZE as f32 / self.sample_rate as f32;
+        self.time += hop_seconds;
+        self.beat *= (-hop_seconds / BEAT_DECAY).exp();
+
+        for i in 0..FRAME_SIZE {
+            self.re[i] = self.pending[i] * self.window[i];
+            self.im[i] = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        // amplitudes, a full scale sine peaks at 1
+        let scale = 2.0 / self.window_sum;
+        let bin_width = self.sample_rate as f32 / FRAME_SIZE as f32;
+        let mut flux = 0.0;
+        let mut band_power = [0.0; 3];
+        for (k, last) in self.last_magnitudes.iter_mut().enumerate() {
+            let magnitude = self.re[k].hypot(self.im[k]) * scale;
+            flux += (magnitude - *last).max(0.0);
+            *last = magnitude;
+
+            let frequency = k as f32 * bin_width;
+            let band = match frequency {
+                f if f < BASS_MAX => 0,
+                f if f < MID_MAX => 1,
+                _ => 2,
+            };
+            band_power[band] += magnitude * magnitude;
+        }
+        self.bands = band_power.map(|power| (power * self.power_scale).sqrt().min(1.0));
+
+        let window_len = (THRESHOLD_WINDOW / hop_seconds).ceil() as usize;
+        let mean = match self.flux_history.len() {
+            0 => 0.0,
+            len => self.flux_history.iter().sum::<f32>() / len as f32,
+        };
+        let threshold = (mean * THRESHOLD_FACTOR).max(THRESHOLD_MIN);
+        let since_onset = self.onsets.back().map_or(f32::INFINITY, |onset| self.time - onset);
+        if flux > threshold && since_onset >= MIN_ONSET_INTERVAL {
+            self.beat = 1.0;
+            self.onsets.push_back(self.time);
+            if self.onsets.len() > ONSET_HISTORY {
+                self.onsets.pop_front();
+            }
+            self.bpm = estimate_bpm(self.onsets.make_contiguous());
+        }
+
+        self.flux_history.push_back(flux);
+        while self.flux_history.len() > window_len {
+            self.flux_history.pop_front();
+        }
+    }
+
+    /// 1 at an onset, falling off until the next one.
+    pub fn beat(&self) -> f32 {
+        self.beat
+    }
+
+    /// Zero until a few onsets came in.
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Amplitude of bass, mid and treble in the last frame.
+    pub fn bands(&self) -> [f32; 3] {
+        self.bands
+    }
+
+    /// Seconds into the audio of every onset kept for the tempo estimate.
+    pub fn onsets(&self) -> impl Iterator<Item = f32> + '_ {
+        self.onsets.iter().copied()
+    }
+
+    /// Writes `beat`, `bpm`, `bass`, `mid` and `treble`.
+    pub fn write_uniform(&self, uniform: &mut Uniform) {
+        uniform.beat = self.beat;
+        uniform.bpm = self.bpm;
+        [uniform.bass, uniform.mid, uniform.treble] = self.bands;
+    }
+}
+
+impl Default for BeatDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    // decaying noise bursts on top of a quiet hum
+    fn click_track(bpm: f32, seconds: f32) -> Vec<f32> {
+        let period = (60.0 / bpm * RATE as f32) as usize;
+        let mut noise = 0x1234_5678u32;
+        (0..(seconds * RATE as f32) as usize)
+            .map(|i| {
+                noise ^= noise << 13;
+                noise ^= noise >> 17;
+                noise ^= noise << 5;
+                let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+                let since_click = (i % period) as f32 / RATE as f32;
+                let hum = 0.05 * (2.0 * PI * 220.0 * i as f32 / RATE as f32).sin();
+                white * 0.8 * (-since_click / 0.01).exp() + hum
+            })
+            .collect()
+    }
+
+    fn detect(samples: &[f32]) -> BeatDetector {
+        let mut detector = BeatDetector::new();
+        // in pieces of the size a frame would read
+        for chunk in samples.chunks(800) {
+            detector.push(chunk, RATE);
+        }
+        detector
+    }
+
+    #[test]
+    fn click_tracks_give_their_tempo() {
+        for bpm in [90.0, 120.0, 150.0] {
+            let detector = detect(&click_track(bpm, 12.0 * 60.0 / bpm));
+
+            // every click but none in between
+            assert_eq!(detector.onsets().count(), 12, "{bpm} bpm");
+            assert!((detector.bpm() - bpm).abs() < 2.0, "{bpm} bpm, estimated {}", detector.bpm());
+        }
+    }
+
+    #[test]
+    fn beat_pulses_and_decays() {
+        let mut detector = BeatDetector::new();
+        let track = click_track(120.0, 1.0);
+        detector.push(&track[..RATE as usize / 20], RATE);
+        assert!(detector.beat() > 0.5);
+
+        // just before the next click
+        detector.push(&track[RATE as usize / 20..RATE as usize * 9 / 20], RATE);
+        assert!(detector.beat() < 0.05);
+    }
+
+    #[test]
+    fn steady_tones_have_no_beat() {
+        let tone: Vec<f32> = (0..RATE as usize * 4)
+            .map(|i| 0.5 * (2.0 * PI * 440.0 * i as f32 / RATE as f32).sin())
+            .collect();
+        let detector = detect(&tone);
+
+        // only the tone starting counts
+        assert!(detector.onsets().count() <= 1);
+        assert_eq!(detector.bpm(), 0.0);
+    }
+
+    #[test]
+    fn bands_follow_frequency() {
+        for (frequency, band) in [(60.0, 0), (1_000.0, 1), (8_000.0, 2)] {
+            let tone: Vec<f32> = (0..FRAME_SIZE * 4)
+                .map(|i| 0.5 * (2.0 * PI * frequency * i as f32 / RATE as f32).sin())
+                .collect();
+            let bands = detect(&tone).bands();
+
+            assert!((bands[band] - 0.5).abs() < 0.05, "{frequency} Hz: {bands:?}");
+            assert!(bands.iter().sum::<f32>() - bands[band] < 0.05, "{frequency} Hz: {bands:?}");
+        }
+    }
+
+    #[test]
+    fn tempo_folds_octaves() {
+        let onsets: Vec<f32> = (0..6).map(|i| i as f32 * 0.25).collect();
+        // 240 bpm reads as 120
+        assert!((estimate_bpm(&onsets) - 120.0).abs() < 1e-3);
+        assert_eq!(estimate_bpm(&onsets[..4]), 0.0);
+    }
+}
@@ -0,0 +1,114 @@
+//! Times frames rendered offscreen, for comparing shader changes without a window
+//! or a display's refresh rate in the way.
+
+use crate::export::frame_time;
+use crate::offscreen::{Offscreen, OFFSCREEN_FORMAT};
+use crate::pipelines::{PipelineError, PipelineKind};
+use crate::scene::{Scene, SceneAssets};
+use crate::shadertoy::ShadertoyInputs;
+use crate::wgpu_core::{self, AdapterSelection};
+use log::info;
+use std::fmt;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use winit::dpi::PhysicalSize;
+
+/// Rendered before timing starts, the first frames pay for lazily created resources.
+pub const WARMUP_FRAMES: u32 = 10;
+/// Time steps like a 60 Hz display, so animated shaders do the same work on every run.
+const BENCH_FPS: u32 = 60;
+
+#[derive(Clone, Debug)]
+pub struct BenchSettings {
+    pub pipeline_kind: PipelineKind,
+    pub size: PhysicalSize<u32>,
+    pub frames: u32,
+    pub assets: SceneAssets,
+    pub adapter: AdapterSelection,
+}
+
+/// Frame times in milliseconds, from submitting a frame until the gpu finished it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchReport {
+    pub frames: usize,
+    pub min: f32,
+    pub mean: f32,
+    pub max: f32,
+}
+
+impl BenchReport {
+    pub fn from_frame_times(times: &[f32]) -> Self {
+        let frames = times.len();
+        Self {
+            frames,
+            min: times.iter().copied().fold(f32::INFINITY, f32::min),
+            mean: times.iter().sum::<f32>() / frames.max(1) as f32,
+            max: times.iter().copied().fold(0.0, f32::max),
+        }
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, mean {:.3} ms ({:.1} fps), min {:.3} ms, max {:.3} ms",
+            self.frames,
+            self.mean,
+            1000.0 / self.mean,
+            self.min,
+            self.max
+        )
+    }
+}
+
+pub async fn run_bench(settings: &BenchSettings) -> Result<BenchReport, PipelineError> {
+    let (_instance, adapter, dev, queue) =
+        wgpu_core::init_headless(&settings.adapter).await.map_err(|e| PipelineError::Device(e.0))?;
+
+    let size = settings.size;
+    let camera = Scene::default_camera(settings.pipeline_kind);
+    let (mut scene, mut uniform) =
+        Scene::with_assets(&dev, settings.pipeline_kind, size, OFFSCREEN_FORMAT, camera.projection(), &settings.assets)?;
+    let offscreen = Offscreen::new(&dev, size);
+
+    info!(
+        "timing {} {:?} frames at {}x{} on {}.",
+        settings.frames, settings.pipeline_kind, size.width, size.height, adapter.get_info().name
+    );
+
+    let mut shadertoy_inputs = ShadertoyInputs::default();
+    let mut times = Vec::with_capacity(settings.frames as usize);
+    for frame in 0..WARMUP_FRAMES + settings.frames {
+        uniform.time = frame_time(frame, BENCH_FPS);
+        camera.write_uniform(&mut uniform, size.width as f32 / size.height as f32);
+        let date = UNIX_EPOCH + Duration::from_millis(uniform.time as u64);
+        shadertoy_inputs.write_uniform(&mut uniform, 1.0 / BENCH_FPS as f32, date);
+        queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let start = Instant::now();
+        let mut encoder = dev.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("bench_encoder"),
+        });
+        scene.render(&mut encoder, &offscreen.view, &offscreen.depth_view);
+        queue.submit(Some(encoder.finish()));
+        dev.poll(wgpu::Maintain::Wait);
+
+        if frame >= WARMUP_FRAMES {
+            times.push(start.elapsed().as_secs_f32() * 1000.0);
+        }
+    }
+
+    Ok(BenchReport::from_frame_times(&times))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summarizes_times() {
+        let report = BenchReport::from_frame_times(&[2.0, 4.0, 3.0, 7.0]);
+        assert_eq!(report, BenchReport { frames: 4, min: 2.0, mean: 4.0, max: 7.0 });
+        assert_eq!(report.to_string(), "4 frames, mean 4.000 ms (250.0 fps), min 2.000 ms, max 7.000 ms");
+    }
+}
@@ -0,0 +1,660 @@
+use crate::projection::Projection;
+use crate::uniform::Uniform;
+use glam::{Mat4, Vec2, Vec3};
+use std::f32::consts::FRAC_PI_2;
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+pub const UP: Vec3 = Vec3::Y;
+
+const MIN_FOV: f32 = 10.0;
+const MAX_FOV: f32 = 120.0;
+// keep away from the poles, where the basis degenerates
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+// the fly camera has no target, orthographic mode frames things this far ahead
+const FLY_FOCUS_DISTANCE: f32 = 2.0;
+
+/// Camera basis the raymarcher builds its rays from.
+///
+/// A pixel at `screen` in `[-aspect, aspect] x [-1, 1]` shoots a ray from `pos`
+/// along `dir + screen.x * plane_u + screen.y * plane_v`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayBasis {
+    pub pos: Vec3,
+    pub dir: Vec3,
+    pub plane_u: Vec3,
+    pub plane_v: Vec3,
+}
+
+impl RayBasis {
+    /// Basis looking along `forward` with a vertical field of view of `fov` degrees.
+    pub fn new(pos: Vec3, forward: Vec3, fov: f32) -> Self {
+        let dir = forward.normalize();
+        let right = UP.cross(dir).normalize();
+        let up = dir.cross(right);
+        let half_height = (fov.to_radians() * 0.5).tan();
+
+        Self {
+            pos,
+            dir,
+            plane_u: right * half_height,
+            plane_v: up * half_height,
+        }
+    }
+
+    pub fn ray_dir(&self, screen: Vec2) -> Vec3 {
+        self.dir + screen.x * self.plane_u + screen.y * self.plane_v
+    }
+
+    /// Screen position a world point maps to, `None` if it lies behind the camera.
+    pub fn project(&self, point: Vec3) -> Option<Vec2> {
+        let rel = point - self.pos;
+        let depth = rel.dot(self.dir);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        Some(Vec2::new(
+            rel.dot(self.plane_u) / (depth * self.plane_u.length_squared()),
+            rel.dot(self.plane_v) / (depth * self.plane_v.length_squared()),
+        ))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct MoveInput {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    fast: bool,
+    slow: bool,
+}
+
+/// First person camera, WASD to move, E / Q to rise and sink, hold the right mouse button
+/// to look around, shift and control speed up or slow down, the wheel changes the fov.
+#[derive(Copy, Clone, Debug)]
+pub struct FlyCamera {
+    pub pos: Vec3,
+    // radians, yaw 0 looks along +z, pitch 0 is level
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: Projection,
+    // units per second and radians per pixel
+    pub speed: f32,
+    pub sensitivity: f32,
+
+    input: MoveInput,
+    looking: bool,
+}
+
+impl FlyCamera {
+    pub fn new(pos: Vec3, target: Vec3) -> Self {
+        let mut camera = Self {
+            pos,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Projection::default(),
+            speed: 1.0,
+            sensitivity: 0.003,
+
+            input: MoveInput::default(),
+            looking: false,
+        };
+        camera.look_at(target);
+
+        camera
+    }
+
+    pub fn look_at(&mut self, target: Vec3) {
+        let dir = (target - self.pos).normalize_or_zero();
+        if dir == Vec3::ZERO {
+            return;
+        }
+
+        self.yaw = dir.x.atan2(dir.z);
+        self.pitch = dir.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw)
+    }
+
+    pub fn right(&self) -> Vec3 {
+        UP.cross(self.forward()).normalize()
+    }
+
+    pub fn ray_basis(&self) -> RayBasis {
+        RayBasis::new(self.pos, self.forward(), self.projection.fov)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_lh(self.pos, self.forward(), UP)
+    }
+
+    pub fn proj(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection.matrix(aspect_ratio, FLY_FOCUS_DISTANCE)
+    }
+
+    pub fn view_proj(&self, aspect_ratio: f32) -> Mat4 {
+        self.proj(aspect_ratio) * self.view()
+    }
+
+    /// Returns true if the key moved the camera.
+    pub fn handle_key(&mut self, event: &KeyEvent) -> bool {
+        let pressed = event.state == ElementState::Pressed;
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return false;
+        };
+
+        let flag = match code {
+            KeyCode::KeyW => &mut self.input.forward,
+            KeyCode::KeyS => &mut self.input.back,
+            KeyCode::KeyA => &mut self.input.left,
+            KeyCode::KeyD => &mut self.input.right,
+            KeyCode::KeyE => &mut self.input.up,
+            KeyCode::KeyQ => &mut self.input.down,
+            KeyCode::ShiftLeft | KeyCode::ShiftRight => &mut self.input.fast,
+            KeyCode::ControlLeft | KeyCode::ControlRight => &mut self.input.slow,
+            _ => return false,
+        };
+        *flag = pressed;
+
+        true
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if button == MouseButton::Right {
+            self.looking = state == ElementState::Pressed;
+        }
+    }
+
+    /// Raw mouse motion in pixels, only applied while looking around.
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        if !self.looking {
+            return;
+        }
+
+        self.rotate(Vec2::new(delta.0 as f32, delta.1 as f32) * self.sensitivity);
+    }
+
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0,
+        };
+
+        self.projection.fov = (self.projection.fov - lines * 2.5).clamp(MIN_FOV, MAX_FOV);
+    }
+
+    /// Yaw right and pitch down by the given angles in radians.
+    pub fn rotate(&mut self, angles: Vec2) {
+        self.yaw += angles.x;
+        self.pitch = (self.pitch - angles.y).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn is_moving(&self) -> bool {
+        let input = &self.input;
+        input.forward || input.back || input.left || input.right || input.up || input.down
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        let axis = |pos: bool, neg: bool| pos as i32 as f32 - neg as i32 as f32;
+
+        let forward = self.forward();
+        let movement = forward * axis(self.input.forward, self.input.back)
+            + self.right() * axis(self.input.right, self.input.left)
+            + UP * axis(self.input.up, self.input.down);
+
+        let mut speed = self.speed;
+        if self.input.fast {
+            speed *= 4.0;
+        }
+        if self.input.slow {
+            speed *= 0.25;
+        }
+
+        self.pos += movement.normalize_or_zero() * speed * delta_time;
+    }
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        // in front of the octree, which resides at [1, 2]
+        Self::new(Vec3::new(1.5, 1.5, -0.5), Vec3::splat(1.5))
+    }
+}
+
+/// Camera circling a target, drag with the left mouse button to rotate,
+/// with the middle button to pan and use the wheel to zoom.
+#[derive(Copy, Clone, Debug)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    // radians, the camera sits at yaw 0 / pitch 0 on the -z side of the target
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: Projection,
+    // radians per pixel, fraction of the distance per pixel and per wheel line
+    pub sensitivity: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+
+    rotating: bool,
+    panning: bool,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Projection::default(),
+            sensitivity: 0.005,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    /// Orbit that reproduces the view of a camera at `pos` looking along `forward`.
+    pub fn from_view(pos: Vec3, forward: Vec3, distance: f32) -> Self {
+        let forward = forward.normalize();
+        let mut camera = Self::new(pos + forward * distance, distance);
+        camera.yaw = forward.x.atan2(forward.z);
+        camera.pitch = forward.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+
+        camera
+    }
+
+    /// Direction from the camera towards the target.
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw)
+    }
+
+    pub fn pos(&self) -> Vec3 {
+        self.target - self.forward() * self.distance
+    }
+
+    pub fn ray_basis(&self) -> RayBasis {
+        RayBasis::new(self.pos(), self.forward(), self.projection.fov)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_lh(self.pos(), self.target, UP)
+    }
+
+    pub fn proj(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection.matrix(aspect_ratio, self.distance)
+    }
+
+    pub fn view_proj(&self, aspect_ratio: f32) -> Mat4 {
+        self.proj(aspect_ratio) * self.view()
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        let pressed = state == ElementState::Pressed;
+        match button {
+            MouseButton::Left => self.rotating = pressed,
+            MouseButton::Middle => self.panning = pressed,
+            _ => {}
+        }
+    }
+
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        let delta = Vec2::new(delta.0 as f32, delta.1 as f32);
+
+        if self.rotating {
+            self.rotate(delta * self.sensitivity);
+        } else if self.panning {
+            self.pan(delta * self.pan_speed * self.distance);
+        }
+    }
+
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0,
+        };
+
+        self.zoom(lines);
+    }
+
+    /// Moves the camera right and down around the target by the given angles in radians.
+    pub fn rotate(&mut self, angles: Vec2) {
+        self.yaw -= angles.x;
+        self.pitch = (self.pitch + angles.y).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Drags the target with the cursor, `offset` is in world units along the screen axes.
+    pub fn pan(&mut self, offset: Vec2) {
+        let right = UP.cross(self.forward()).normalize();
+        let up = self.forward().cross(right);
+
+        self.target += -right * offset.x + up * offset.y;
+    }
+
+    /// Positive steps move closer, each step scales the distance by `zoom_speed`.
+    pub fn zoom(&mut self, steps: f32) {
+        let distance = self.distance * (1.0 - self.zoom_speed).powf(steps);
+        self.distance = distance.clamp(self.projection.near * 2.0, self.projection.far * 0.5);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Fly,
+    Orbit,
+}
+
+/// Both camera kinds, switched with tab. The active one drives the uniform,
+/// switching keeps the current view. P toggles between perspective and orthographic.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub fly: FlyCamera,
+    pub orbit: OrbitCamera,
+}
+
+impl Camera {
+    pub fn new(mode: CameraMode, pos: Vec3, target: Vec3) -> Self {
+        Self {
+            mode,
+            fly: FlyCamera::new(pos, target),
+            orbit: OrbitCamera::from_view(pos, target - pos, pos.distance(target)),
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        match self.mode {
+            CameraMode::Fly => {
+                // orbit around whatever lies the same distance ahead as the last target
+                let (projection, distance) = (self.fly.projection, self.orbit.distance);
+                self.orbit = OrbitCamera {
+                    projection,
+                    ..OrbitCamera::from_view(self.fly.pos, self.fly.forward(), distance)
+                };
+                self.mode = CameraMode::Orbit;
+            }
+            CameraMode::Orbit => {
+                self.fly.pos = self.orbit.pos();
+                self.fly.look_at(self.orbit.target);
+                self.fly.projection = self.orbit.projection;
+                self.mode = CameraMode::Fly;
+            }
+        }
+    }
+
+    pub fn projection(&self) -> &Projection {
+        match self.mode {
+            CameraMode::Fly => &self.fly.projection,
+            CameraMode::Orbit => &self.orbit.projection,
+        }
+    }
+
+    pub fn toggle_projection(&mut self) {
+        self.fly.projection.toggle_kind();
+        self.orbit.projection.toggle_kind();
+    }
+
+    pub fn ray_basis(&self) -> RayBasis {
+        match self.mode {
+            CameraMode::Fly => self.fly.ray_basis(),
+            CameraMode::Orbit => self.orbit.ray_basis(),
+        }
+    }
+
+    pub fn view_proj(&self, aspect_ratio: f32) -> Mat4 {
+        match self.mode {
+            CameraMode::Fly => self.fly.view_proj(aspect_ratio),
+            CameraMode::Orbit => self.orbit.view_proj(aspect_ratio),
+        }
+    }
+
+    /// Returns true if the key was consumed by the camera.
+    pub fn handle_key(&mut self, event: &KeyEvent) -> bool {
+        if event.physical_key == PhysicalKey::Code(KeyCode::Tab) {
+            if event.state == ElementState::Pressed && !event.repeat {
+                self.toggle_mode();
+            }
+            return true;
+        }
+
+        if event.physical_key == PhysicalKey::Code(KeyCode::KeyP) {
+            if event.state == ElementState::Pressed && !event.repeat {
+                self.toggle_projection();
+            }
+            return true;
+        }
+
+        // keep tracking held keys in orbit mode, so nothing is stuck after switching back
+        self.fly.handle_key(event)
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        match self.mode {
+            CameraMode::Fly => self.fly.handle_mouse_button(button, state),
+            CameraMode::Orbit => self.orbit.handle_mouse_button(button, state),
+        }
+    }
+
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        match self.mode {
+            CameraMode::Fly => self.fly.handle_mouse_motion(delta),
+            CameraMode::Orbit => self.orbit.handle_mouse_motion(delta),
+        }
+    }
+
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        match self.mode {
+            CameraMode::Fly => self.fly.handle_scroll(delta),
+            CameraMode::Orbit => self.orbit.handle_scroll(delta),
+        }
+    }
+
+    /// Held movement keys, the view changes every frame until they are released.
+    pub fn is_moving(&self) -> bool {
+        self.mode == CameraMode::Fly && self.fly.is_moving()
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if self.mode == CameraMode::Fly {
+            self.fly.update(delta_time);
+        }
+    }
+
+    pub fn write_uniform(&self, uniform: &mut Uniform, aspect_ratio: f32) {
+        uniform.proj = self.view_proj(aspect_ratio).to_cols_array_2d();
+        uniform.update_ray_basis(&self.ray_basis());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::ProjectionKind;
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{a} != {b}");
+    }
+
+    #[test]
+    fn default_orientation() {
+        let camera = FlyCamera::new(Vec3::ZERO, Vec3::Z);
+        let basis = camera.ray_basis();
+        let half_height = 30.0f32.to_radians().tan();
+
+        assert_vec_eq(basis.dir, Vec3::Z);
+        assert_vec_eq(basis.plane_u, Vec3::X * half_height);
+        assert_vec_eq(basis.plane_v, Vec3::Y * half_height);
+    }
+
+    #[test]
+    fn basis_is_orthogonal() {
+        let mut camera = FlyCamera::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(-4.0, 0.5, 2.0));
+        camera.rotate(Vec2::new(0.7, -0.3));
+        let basis = camera.ray_basis();
+
+        assert!((basis.dir.length() - 1.0).abs() < 1e-5);
+        assert!(basis.dir.dot(basis.plane_u).abs() < 1e-5);
+        assert!(basis.dir.dot(basis.plane_v).abs() < 1e-5);
+        assert!(basis.plane_u.dot(basis.plane_v).abs() < 1e-5);
+        assert!(basis.plane_u.y.abs() < 1e-5, "horizon must stay level");
+        assert!(basis.plane_v.y > 0.0, "plane_v must point up");
+    }
+
+    #[test]
+    fn look_at_targets_point() {
+        let pos = Vec3::new(1.0, 2.0, 3.0);
+        let target = Vec3::new(4.0, 1.0, -2.0);
+        let camera = FlyCamera::new(pos, target);
+
+        assert_vec_eq(camera.forward(), (target - pos).normalize());
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = FlyCamera::default();
+        camera.rotate(Vec2::new(0.0, -10.0));
+        assert!(camera.pitch <= MAX_PITCH);
+        assert!(camera.ray_basis().plane_u.is_finite());
+    }
+
+    #[test]
+    fn project_inverts_ray_dir() {
+        let camera = FlyCamera::new(Vec3::new(0.5, 1.0, -2.0), Vec3::new(1.0, 0.0, 3.0));
+        let basis = camera.ray_basis();
+
+        for screen in [Vec2::ZERO, Vec2::new(1.2, -0.4), Vec2::new(-0.8, 0.9)] {
+            let point = basis.pos + basis.ray_dir(screen) * 3.5;
+            assert!(basis.project(point).unwrap().abs_diff_eq(screen, 1e-4));
+        }
+
+        assert_eq!(basis.project(basis.pos - basis.dir), None);
+    }
+
+    #[test]
+    fn matrix_agrees_with_ray_basis() {
+        let camera = FlyCamera::new(Vec3::new(-1.0, 0.5, 0.0), Vec3::new(2.0, 1.0, 5.0));
+        let aspect_ratio = 16.0 / 9.0;
+        let basis = camera.ray_basis();
+        let view_proj = camera.view_proj(aspect_ratio);
+
+        for screen in [Vec2::new(0.3, 0.2), Vec2::new(-1.5, -0.9), Vec2::new(1.7, 0.6)] {
+            let point = basis.pos + basis.ray_dir(screen) * 2.0;
+            let clip = view_proj.project_point3(point);
+
+            // ndc x is normalized by the aspect ratio, the ray basis is not
+            assert!((clip.x * aspect_ratio - screen.x).abs() < 1e-4);
+            assert!((clip.y - screen.y).abs() < 1e-4);
+            assert!((0.0..=1.0).contains(&clip.z));
+        }
+    }
+
+    #[test]
+    fn orthographic_keeps_target_centered() {
+        let target = Vec3::new(0.5, 0.5, 0.5);
+        let mut camera = Camera::new(CameraMode::Orbit, Vec3::new(2.0, 1.8, -1.5), target);
+        camera.toggle_projection();
+        assert_eq!(camera.projection().kind, ProjectionKind::Orthographic);
+
+        let clip = camera.view_proj(1.5).project_point3(target);
+        assert!(clip.truncate().abs_diff_eq(Vec2::ZERO, 1e-5));
+        assert!((0.0..=1.0).contains(&clip.z));
+
+        camera.toggle_mode();
+        assert_eq!(camera.projection().kind, ProjectionKind::Orthographic);
+    }
+
+    #[test]
+    fn movement_follows_orientation() {
+        let mut camera = FlyCamera::new(Vec3::ZERO, Vec3::X);
+        camera.input.forward = true;
+        camera.update(2.0);
+        assert_vec_eq(camera.pos, Vec3::X * 2.0);
+
+        camera.input.fast = true;
+        camera.update(1.0);
+        assert_vec_eq(camera.pos, Vec3::X * 6.0);
+
+        camera.input = MoveInput {
+            right: true,
+            ..Default::default()
+        };
+        camera.update(1.0);
+        // looking along +x, right is -z in a left handed system
+        assert_vec_eq(camera.pos, Vec3::new(6.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn fov_is_clamped() {
+        let mut camera = FlyCamera::default();
+        camera.handle_scroll(MouseScrollDelta::LineDelta(0.0, 100.0));
+        assert_eq!(camera.projection.fov, MIN_FOV);
+        camera.handle_scroll(MouseScrollDelta::LineDelta(0.0, -100.0));
+        assert_eq!(camera.projection.fov, MAX_FOV);
+    }
+
+    #[test]
+    fn orbit_looks_at_target() {
+        let target = Vec3::new(0.5, 0.5, 0.5);
+        let mut camera = OrbitCamera::new(target, 3.0);
+        assert_vec_eq(camera.pos(), Vec3::new(0.5, 0.5, -2.5));
+
+        camera.rotate(Vec2::new(1.1, 0.4));
+        assert!((camera.pos().distance(target) - 3.0).abs() < 1e-5);
+        assert_vec_eq(camera.ray_basis().dir, (target - camera.pos()).normalize());
+
+        // the target always projects to the screen center
+        let clip = camera.view_proj(1.5).project_point3(target);
+        assert!(clip.truncate().abs_diff_eq(Vec2::ZERO, 1e-5));
+    }
+
+    #[test]
+    fn orbit_zoom_and_pan() {
+        let mut camera = OrbitCamera::new(Vec3::ZERO, 2.0);
+        camera.zoom(1.0);
+        assert!((camera.distance - 1.8).abs() < 1e-5);
+        camera.zoom(-1000.0);
+        assert_eq!(camera.distance, camera.projection.far * 0.5);
+
+        let mut camera = OrbitCamera::new(Vec3::ZERO, 2.0);
+        let forward = camera.forward();
+        camera.pan(Vec2::new(1.0, 1.0));
+        assert_vec_eq(camera.target, Vec3::new(-1.0, 1.0, 0.0));
+        assert_vec_eq(camera.forward(), forward);
+    }
+
+    #[test]
+    fn switching_keeps_the_view() {
+        let mut camera = Camera::new(CameraMode::Fly, Vec3::new(1.0, 2.0, -3.0), Vec3::ZERO);
+        camera.fly.rotate(Vec2::new(0.3, 0.1));
+        let before = camera.ray_basis();
+
+        camera.toggle_mode();
+        assert_eq!(camera.mode, CameraMode::Orbit);
+        let after = camera.ray_basis();
+        assert_vec_eq(after.pos, before.pos);
+        assert_vec_eq(after.dir, before.dir);
+
+        camera.orbit.rotate(Vec2::new(-0.5, 0.2));
+        let before = camera.ray_basis();
+        camera.toggle_mode();
+        assert_eq!(camera.mode, CameraMode::Fly);
+        let after = camera.ray_basis();
+        assert_vec_eq(after.pos, before.pos);
+        assert_vec_eq(after.dir, before.dir);
+    }
+}
@@ -0,0 +1,432 @@
+//! Command line parsing. Every command has a `--help`, `HELP` lists them.
+
+use crate::config::{parse_backends, parse_log_level, parse_power_preference, ConfigError, Overrides, CONFIG_PATH};
+use crate::pipelines::PipelineKind;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use winit::dpi::LogicalSize;
+
+pub const HELP: &str = "\
+usage: patibu [command] [options]
+
+commands:
+  run        open a window, the default
+  render     render frames without a window
+  bench      time frames rendered without a window
+  convert    turn a MagicaVoxel .vox model into an .svo scene
+  devices    list graphics adapters and audio devices
+  help       show this or the help of a command
+
+options for every command:
+  --config <file>        read and save settings there, default patibu.toml
+  --log-level <level>    off, error, warn, info, debug or trace
+  -h, --help             show the help of the command
+
+`patibu <command> --help` shows the options of a command.
+";
+
+const SCENE_OPTIONS: &str = "\
+  [pipeline], -p, --pipeline <pipeline>
+                         mesh, octree or shadertoy, default mesh
+  -s, --size <WxH>       e.g. 1920x1080, default the window size from the config
+  --shaders <dir>        read shaders below <dir> instead of the binary, e.g. a checkout
+  --scene <file.svo>     octree for the octree pipeline, see `patibu convert`
+  --backend <backend>    vulkan, gl, metal, dx12, primary or all
+  --power <preference>   none, low_power or high_performance
+  --adapter <adapter>    an index from `patibu devices`, part of a name or software
+";
+
+pub const RUN_HELP: &str = "\
+usage: patibu [run] [options]
+
+opens a window, shaders are reloaded when they change on disk.
+
+options:
+  --audio <file.wav>     play a file instead of capturing the audio input
+";
+
+pub const RENDER_HELP: &str = "\
+usage: patibu render [options]
+
+renders without a window, the same options always give the same output.
+
+options:
+  -o, --output <path>    file.png renders one frame, file.y4m a video stream and
+                         anything else a directory of pngs, default headless.png
+  --frames <n>           frames for a video or png directory, default 300
+  --fps <n>              frames per second of uniform.time, default 60
+  --audio <file.wav>     drive audio reactive shaders with a file
+";
+
+pub const BENCH_HELP: &str = "\
+usage: patibu bench [options]
+
+renders frames without a window and reports how long the gpu took.
+
+options:
+  --frames <n>           frames to time after a short warmup, default 600
+";
+
+pub const CONVERT_HELP: &str = "\
+usage: patibu convert <input.vox> <output.svo>
+
+turns the first model of a MagicaVoxel file into an octree for `--scene`,
+z up becomes y up and palette indices become materials.
+";
+
+pub const DEVICES_HELP: &str = "\
+usage: patibu devices [options]
+
+lists graphics adapters with their backend, type and limits and audio hosts with their
+input devices. adapters are numbered for `--adapter`, which counts within the backend
+from the config or `--backend`.
+
+options:
+  --backend <backend>    list the adapters of other backends, e.g. all
+";
+
+pub const DEFAULT_OUTPUT: &str = "headless.png";
+pub const DEFAULT_RENDER_FRAMES: u32 = 300;
+pub const DEFAULT_BENCH_FRAMES: u32 = 600;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CliError(pub String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// What `run`, `render` and `bench` draw.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneArgs {
+    pub pipeline_kind: PipelineKind,
+    /// crate root to read the shaders from
+    pub shaders: Option<PathBuf>,
+    /// `.svo` file for the octree pipeline
+    pub scene: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Run {
+        scene: SceneArgs,
+        audio: Option<PathBuf>,
+    },
+    Render {
+        scene: SceneArgs,
+        output: PathBuf,
+        frames: u32,
+        fps: u32,
+        audio: Option<PathBuf>,
+    },
+    Bench {
+        scene: SceneArgs,
+        frames: u32,
+    },
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    Devices,
+    /// text to print
+    Help(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub config: PathBuf,
+    pub overrides: Overrides,
+}
+
+/// `WxH`, e.g. `1920x1080`.
+struct Resolution(LogicalSize<u32>);
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+
+        match parsed {
+            Some((w, h)) if w > 0 && h > 0 => Ok(Resolution(LogicalSize::new(w, h))),
+            _ => Err(format!("invalid resolution '{s}', expected e.g. 1920x1080.")),
+        }
+    }
+}
+
+const COMMANDS: [(&str, &str); 6] = [
+    ("run", RUN_HELP),
+    ("render", RENDER_HELP),
+    ("bench", BENCH_HELP),
+    ("convert", CONVERT_HELP),
+    ("devices", DEVICES_HELP),
+    ("help", HELP),
+];
+
+/// Help of a command, a full text for the ones that share the scene options.
+pub fn help(command: &str) -> Option<String> {
+    let (_, text) = COMMANDS.iter().find(|(name, _)| *name == command)?;
+    let scene_options = match command {
+        "run" | "render" | "bench" => SCENE_OPTIONS,
+        _ => "",
+    };
+
+    Some(format!("{text}{scene_options}"))
+}
+
+// which options a command takes besides the global ones
+fn takes(command: &str, option: &str) -> bool {
+    let scene = ["--pipeline", "--size", "--shaders", "--scene", "--backend", "--power", "--adapter"];
+    match command {
+        "run" => scene.contains(&option) || option == "--audio",
+        "render" => scene.contains(&option) || ["--output", "--frames", "--fps", "--audio"].contains(&option),
+        "bench" => scene.contains(&option) || option == "--frames",
+        "devices" => option == "--backend",
+        _ => false,
+    }
+}
+
+fn long_name(option: &str) -> &str {
+    match option {
+        "-p" => "--pipeline",
+        "-s" => "--size",
+        "-o" => "--output",
+        "-h" => "--help",
+        option => option,
+    }
+}
+
+// options whose value is checked by a parser from `config`
+fn parse_name<T>(option: &str, value: Option<String>, parser: fn(&str) -> Result<T, ConfigError>) -> Result<T, CliError> {
+    parser(&parse_value::<String>(option, value)?).map_err(|e| CliError(format!("{option}: {e}")))
+}
+
+fn parse_value<T>(option: &str, value: Option<String>) -> Result<T, CliError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = value.ok_or_else(|| CliError(format!("{option} expects a value.")))?;
+    value.parse().map_err(|e| CliError(format!("{option} {value}: {e}")))
+}
+
+// frame counts and rates, zero would render nothing or divide by it
+fn parse_count(option: &str, value: Option<String>) -> Result<u32, CliError> {
+    match parse_value(option, value)? {
+        0 => Err(CliError(format!("{option} must be at least 1."))),
+        count => Ok(count),
+    }
+}
+
+/// Parses the arguments after the program name.
+pub fn parse<I>(args: I) -> Result<Cli, CliError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    let (command, explicit) = match args.peek() {
+        Some(arg) if COMMANDS.iter().any(|(name, _)| name == arg) => (args.next().expect("just peeked."), true),
+        _ => ("run".to_string(), false),
+    };
+
+    let mut config = PathBuf::from(CONFIG_PATH);
+    let mut overrides = Overrides::default();
+    let mut scene = SceneArgs::default();
+    let mut audio = None;
+    let mut output = None;
+    let mut frames = None;
+    let mut fps = 60;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        // `--size=1920x1080` is `--size 1920x1080`
+        let (option, mut value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let option = long_name(&option);
+        if !option.starts_with('-') || option == "-" {
+            positional.push(arg);
+            continue;
+        }
+
+        if option == "--help" {
+            // `patibu --help` is about all commands, not just `run`
+            let text = if explicit { help(&command).expect("the command is known.") } else { HELP.to_string() };
+            return Ok(Cli { command: Command::Help(text), config, overrides });
+        }
+        if !matches!(option, "--config" | "--log-level") && !takes(&command, option) {
+            return Err(CliError(format!("{command} doesn't take {option}, see `patibu {command} --help`.")));
+        }
+
+        let mut value = || value.take().or_else(|| args.next());
+        match option {
+            "--config" => config = parse_value(option, value())?,
+            "--log-level" => overrides.log_level = Some(parse_name(option, value(), parse_log_level)?),
+            "--backend" => overrides.backends = Some(parse_name(option, value(), parse_backends)?),
+            "--power" => overrides.power_preference = Some(parse_name(option, value(), parse_power_preference)?),
+            "--adapter" => overrides.adapter = Some(parse_value(option, value())?),
+            "--size" => overrides.window_size = Some(parse_value::<Resolution>(option, value())?.0),
+            "--pipeline" => scene.pipeline_kind = parse_value(option, value())?,
+            "--shaders" => scene.shaders = Some(parse_value(option, value())?),
+            "--scene" => scene.scene = Some(parse_value(option, value())?),
+            "--audio" => audio = Some(parse_value(option, value())?),
+            "--output" => output = Some(parse_value::<PathBuf>(option, value())?),
+            "--frames" => frames = Some(parse_count(option, value())?),
+            "--fps" => fps = parse_count(option, value())?,
+            _ => unreachable!("only known options are taken."),
+        }
+    }
+
+    let unexpected = |positional: &[String]| {
+        CliError(format!("unexpected arguments {}, see `patibu {command} --help`.", positional.join(" ")))
+    };
+    let command = match command.as_str() {
+        "help" => match positional.as_slice() {
+            [] => Command::Help(HELP.to_string()),
+            [name] => Command::Help(help(name).ok_or_else(|| CliError(format!("unknown command {name}, see `patibu --help`.")))?),
+            _ => return Err(unexpected(&positional)),
+        },
+        "convert" => match <[String; 2]>::try_from(positional) {
+            Ok([input, output]) => Command::Convert {
+                input: input.into(),
+                output: output.into(),
+            },
+            Err(_) => return Err(CliError("convert takes an input and an output file, see `patibu convert --help`.".to_string())),
+        },
+        "devices" if positional.is_empty() => Command::Devices,
+        "devices" => return Err(unexpected(&positional)),
+        command => {
+            // a bare pipeline, e.g. `patibu octree`
+            match positional.as_slice() {
+                [] => {}
+                [pipeline] => scene.pipeline_kind = pipeline.parse().map_err(CliError)?,
+                _ => return Err(unexpected(&positional)),
+            }
+
+            match command {
+                "run" => Command::Run { scene, audio },
+                "render" => {
+                    let output = output.unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT));
+                    let single = output.extension().is_some_and(|ext| ext == "png");
+                    if single && frames.is_some_and(|frames| frames != 1) {
+                        return Err(CliError(
+                            "a .png output holds one frame, give a directory or a .y4m file for --frames.".to_string(),
+                        ));
+                    }
+                    let frames = if single { 1 } else { frames.unwrap_or(DEFAULT_RENDER_FRAMES) };
+                    Command::Render { scene, output, frames, fps, audio }
+                }
+                "bench" => Command::Bench {
+                    scene,
+                    frames: frames.unwrap_or(DEFAULT_BENCH_FRAMES),
+                },
+                _ => unreachable!("every command is handled."),
+            }
+        }
+    };
+
+    Ok(Cli { command, config, overrides })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgpu_core::AdapterChoice;
+    use log::LevelFilter;
+    use wgpu::{Backends, PowerPreference};
+
+    fn parse_args(args: &str) -> Result<Cli, CliError> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    fn command(args: &str) -> Command {
+        parse_args(args).unwrap().command
+    }
+
+    fn error(args: &str) -> String {
+        parse_args(args).unwrap_err().0
+    }
+
+    #[test]
+    fn runs_by_default() {
+        assert_eq!(command(""), Command::Run { scene: SceneArgs::default(), audio: None });
+
+        let scene = SceneArgs { pipeline_kind: PipelineKind::Octree, ..Default::default() };
+        assert_eq!(command("octree"), Command::Run { scene: scene.clone(), audio: None });
+        assert_eq!(command("run -p octree"), Command::Run { scene, audio: None });
+    }
+
+    #[test]
+    fn parses_options() {
+        let cli = parse_args("run shadertoy --size=800x600 --backend gl --log-level debug --config a.toml --shaders . --audio a.wav").unwrap();
+        assert_eq!(cli.config, PathBuf::from("a.toml"));
+        assert_eq!(cli.overrides.window_size, Some(LogicalSize::new(800, 600)));
+        assert_eq!(cli.overrides.backends, Some(Backends::GL));
+        assert_eq!(cli.overrides.log_level, Some(LevelFilter::Debug));
+        let scene = SceneArgs { pipeline_kind: PipelineKind::Shadertoy, shaders: Some(".".into()), scene: None };
+        assert_eq!(cli.command, Command::Run { scene, audio: Some("a.wav".into()) });
+
+        assert_eq!(
+            command("bench --scene model.svo --frames 50"),
+            Command::Bench { scene: SceneArgs { scene: Some("model.svo".into()), ..Default::default() }, frames: 50 }
+        );
+        assert_eq!(command("convert in.vox out.svo"), Command::Convert { input: "in.vox".into(), output: "out.svo".into() });
+        assert_eq!(command("devices --backend all"), Command::Devices);
+
+        let cli = parse_args("render --adapter 1 --power low_power").unwrap();
+        assert_eq!(cli.overrides.adapter, Some(AdapterChoice::Index(1)));
+        assert_eq!(cli.overrides.power_preference, Some(PowerPreference::LowPower));
+        assert_eq!(parse_args("bench --adapter=NVIDIA").unwrap().overrides.adapter, Some(AdapterChoice::Name("NVIDIA".into())));
+    }
+
+    #[test]
+    fn render_output_decides_frames() {
+        let render = |args: &str| match command(args) {
+            Command::Render { output, frames, fps, .. } => (output, frames, fps),
+            other => panic!("{other:?}"),
+        };
+
+        assert_eq!(render("render"), (PathBuf::from(DEFAULT_OUTPUT), 1, 60));
+        assert_eq!(render("render -o out.y4m --fps 30"), (PathBuf::from("out.y4m"), DEFAULT_RENDER_FRAMES, 30));
+        assert_eq!(render("render --frames 10 -o frames"), (PathBuf::from("frames"), 10, 60));
+        assert!(error("render --frames 10").contains("one frame"));
+    }
+
+    #[test]
+    fn shows_help() {
+        assert_eq!(command("--help"), Command::Help(HELP.to_string()));
+        assert_eq!(command("help"), Command::Help(HELP.to_string()));
+        assert_eq!(command("help render"), command("render -h"));
+        match command("bench --help") {
+            Command::Help(text) => assert!(text.starts_with("usage: patibu bench") && text.contains("--scene")),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn explains_mistakes() {
+        assert!(error("render --audio").contains("--audio expects a value"));
+        assert!(error("devices --adapter 0").contains("devices doesn't take --adapter"));
+        assert!(error("--size").contains("--size expects a value"));
+        assert!(error("--size 0x10").contains("invalid resolution"));
+        assert!(error("--backend glide").contains("--backend: unknown backend glide"));
+        assert!(error("--frames x").contains("run doesn't take --frames"));
+        assert!(error("bench --frames x").contains("--frames x"));
+        assert!(error("bench --frames 0").contains("--frames must be at least 1"));
+        assert!(error("render --frames 0").contains("--frames must be at least 1"));
+        assert!(error("render --fps 0").contains("--fps must be at least 1"));
+        assert!(error("voxels").contains("unknown pipeline 'voxels'"));
+        assert!(error("mesh octree").contains("unexpected arguments mesh octree"));
+        assert!(error("convert in.vox").contains("an input and an output"));
+        assert!(error("help nothing").contains("unknown command nothing"));
+    }
+}
@@ -0,0 +1,573 @@
+//! Settings kept between runs in a toml file next to where patibu is started:
+//!
+//! ```toml
+//! [window]
+//! width = 1280
+//! height = 720
+//!
+//! [graphics]
+//! backend = "vulkan"
+//! power_preference = "high_performance"
+//! # an index from `patibu devices`, part of a name or software, picked by power_preference if unset
+//! adapter = "nvidia"
+//! # fifo, mailbox, immediate or auto_vsync, fifo is used where the surface lacks it
+//! present_mode = "fifo"
+//! # unlimited if unset
+//! max_fps = 144
+//! # only redraw on input and while the picture changes on its own, e.g. audio comes in
+//! low_power = false
+//!
+//! [graphics.clear_color]
+//! r = 0.1
+//! g = 0.2
+//! b = 0.3
+//! a = 1.0
+//!
+//! [log]
+//! level = "info"
+//!
+//! [imgui]
+//! ini = "imgui.ini"
+//! ```
+//!
+//! Every key is optional. Changes made in the ui are written back into the file, comments stay.
+
+use crate::audio::AudioSelection;
+use crate::scene::CLEAR_COLOR;
+use crate::wgpu_core::{AdapterChoice, AdapterSelection};
+use log::LevelFilter;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+use toml_edit::DocumentMut;
+use wgpu::{Backends, Color, PowerPreference, PresentMode};
+use winit::dpi::LogicalSize;
+
+pub const CONFIG_PATH: &str = "patibu.toml";
+/// Largest window side accepted, what most gpus can still render to.
+pub const MAX_WINDOW_SIDE: u32 = 16_384;
+pub const MAX_FPS: u32 = 1000;
+
+const BACKENDS: [(&str, Backends); 6] = [
+    ("vulkan", Backends::VULKAN),
+    ("gl", Backends::GL),
+    ("metal", Backends::METAL),
+    ("dx12", Backends::DX12),
+    ("primary", Backends::PRIMARY),
+    ("all", Backends::all()),
+];
+
+pub const PRESENT_MODES: [(&str, PresentMode); 4] = [
+    ("fifo", PresentMode::Fifo),
+    ("mailbox", PresentMode::Mailbox),
+    ("immediate", PresentMode::Immediate),
+    ("auto_vsync", PresentMode::AutoVsync),
+];
+
+const POWER_PREFERENCES: [(&str, PowerPreference); 3] = [
+    ("none", PowerPreference::None),
+    ("low_power", PowerPreference::LowPower),
+    ("high_performance", PowerPreference::HighPerformance),
+];
+
+pub const LOG_LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn names<T>(options: &[(&str, T)]) -> String {
+    options.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+}
+
+fn by_name<T: Copy>(options: &[(&str, T)], name: &str, what: &str) -> Result<T, ConfigError> {
+    options
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, value)| value)
+        .ok_or_else(|| ConfigError(format!("unknown {what} {name}, expected one of {}.", names(options))))
+}
+
+fn name_of<T: PartialEq>(options: &[(&'static str, T)], value: &T) -> &'static str {
+    options
+        .iter()
+        .find(|(_, v)| v == value)
+        .map(|(name, _)| *name)
+        .expect("only values from the list are ever set.")
+}
+
+/// `vulkan`, `gl`, `metal`, `dx12`, `primary` or `all`.
+pub fn parse_backends(name: &str) -> Result<Backends, ConfigError> {
+    by_name(&BACKENDS, name, "backend")
+}
+
+/// `none`, `low_power` or `high_performance`.
+pub fn parse_power_preference(name: &str) -> Result<PowerPreference, ConfigError> {
+    by_name(&POWER_PREFERENCES, name, "power preference")
+}
+
+/// `fifo`, `mailbox`, `immediate` or `auto_vsync`.
+pub fn parse_present_mode(name: &str) -> Result<PresentMode, ConfigError> {
+    by_name(&PRESENT_MODES, name, "present mode")
+}
+
+/// A whole toml file, the error names the line that is wrong. Also reads shadertoy projects.
+pub fn parse_toml(source: &str) -> Result<Table, String> {
+    source.parse().map_err(|e: toml::de::Error| {
+        let line = e.span().map_or(1, |span| source[..span.start].matches('\n').count() + 1);
+        match e.message() {
+            "" => format!("line {line}: invalid toml."),
+            message => format!("line {line}: {message}."),
+        }
+    })
+}
+
+pub fn parse_log_level(name: &str) -> Result<LevelFilter, ConfigError> {
+    LevelFilter::from_str(name).map_err(|_| {
+        let levels: Vec<String> = LOG_LEVELS.iter().map(|l| l.as_str().to_lowercase()).collect();
+        ConfigError(format!("unknown log level {name}, expected one of {}.", levels.join(", ")))
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub window_size: LogicalSize<u32>,
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    pub adapter: Option<AdapterChoice>,
+    pub present_mode: PresentMode,
+    /// `None` draws as fast as the present mode allows
+    pub max_fps: Option<u32>,
+    pub low_power: bool,
+    pub clear_color: Color,
+    pub log_level: LevelFilter,
+    /// where imgui keeps its window layout, `None` forgets it on exit
+    pub imgui_ini: Option<PathBuf>,
+    pub audio: AudioSelection,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let selection = AdapterSelection::default();
+        Self {
+            window_size: LogicalSize::new(1280, 720),
+            backends: selection.backends,
+            power_preference: selection.power_preference,
+            adapter: selection.adapter,
+            present_mode: PresentMode::Fifo,
+            max_fps: None,
+            low_power: false,
+            clear_color: CLEAR_COLOR,
+            log_level: LevelFilter::Info,
+            imgui_ini: None,
+            audio: AudioSelection::default(),
+        }
+    }
+}
+
+/// Set from the command line, they apply to one run and are never saved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overrides {
+    pub window_size: Option<LogicalSize<u32>>,
+    pub backends: Option<Backends>,
+    pub power_preference: Option<PowerPreference>,
+    pub adapter: Option<AdapterChoice>,
+    pub log_level: Option<LevelFilter>,
+}
+
+// a table and its dotted name for the errors
+struct Section<'a> {
+    name: &'a str,
+    table: &'a Table,
+}
+
+impl<'a> Section<'a> {
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.table.get(key)
+    }
+}
+
+fn section<'a>(name: &'a str, value: &'a Value) -> Result<Section<'a>, ConfigError> {
+    match value {
+        Value::Table(table) => Ok(Section { name, table }),
+        _ => Err(ConfigError(format!("{name} should be a table, got {} {value}.", value.type_str()))),
+    }
+}
+
+fn check_keys(table: &Section, known: &[&str]) -> Result<(), ConfigError> {
+    match table.table.keys().find(|key| !known.contains(&key.as_str())) {
+        Some(key) => Err(ConfigError(format!(
+            "unknown key {key} in [{}], expected one of {}.",
+            table.name,
+            known.join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn invalid(table: &Section, key: &str, expected: &str) -> ConfigError {
+    let value = table.get(key).expect("only keys that are set are checked.");
+    ConfigError(format!("{}.{key} should be {expected}, got {} {value}.", table.name, value.type_str()))
+}
+
+fn string<'a>(table: &Section<'a>, key: &str) -> Result<Option<&'a str>, ConfigError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(invalid(table, key, "a string")),
+    }
+}
+
+fn window_side(table: &Section, key: &str) -> Result<Option<u32>, ConfigError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(&Value::Integer(side)) if (1..=MAX_WINDOW_SIDE as i64).contains(&side) => Ok(Some(side as u32)),
+        Some(_) => Err(invalid(table, key, &format!("an integer from 1 to {MAX_WINDOW_SIDE}"))),
+    }
+}
+
+fn boolean(table: &Section, key: &str) -> Result<Option<bool>, ConfigError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(&Value::Boolean(b)) => Ok(Some(b)),
+        Some(_) => Err(invalid(table, key, "true or false")),
+    }
+}
+
+fn max_fps(table: &Section) -> Result<Option<u32>, ConfigError> {
+    match table.get("max_fps") {
+        None => Ok(None),
+        Some(&Value::Integer(fps)) if (1..=MAX_FPS as i64).contains(&fps) => Ok(Some(fps as u32)),
+        Some(_) => Err(invalid(table, "max_fps", &format!("an integer from 1 to {MAX_FPS}"))),
+    }
+}
+
+fn adapter(table: &Section) -> Result<Option<AdapterChoice>, ConfigError> {
+    match table.get("adapter") {
+        None => Ok(None),
+        Some(&Value::Integer(index)) if index >= 0 => Ok(Some(AdapterChoice::Index(index as usize))),
+        Some(Value::String(name)) if !name.trim().is_empty() => match name.parse() {
+            Ok(AdapterChoice::Index(_)) | Err(_) => Ok(Some(AdapterChoice::Name(name.clone()))),
+            Ok(choice) => Ok(Some(choice)),
+        },
+        Some(_) => Err(invalid(table, "adapter", "an adapter index or name")),
+    }
+}
+
+// integers are fine as well, `a = 1` reads as a float of 1 here
+fn color_component(table: &Section, key: &str) -> Result<Option<f64>, ConfigError> {
+    let component = match table.get(key) {
+        None => return Ok(None),
+        Some(&Value::Float(x)) => x,
+        Some(&Value::Integer(i)) => i as f64,
+        Some(_) => f64::NAN,
+    };
+
+    match component {
+        c if (0.0..=1.0).contains(&c) => Ok(Some(c)),
+        _ => Err(invalid(table, key, "a number from 0 to 1")),
+    }
+}
+
+// sets `edit` to `table`, values that stay keep their comments and keys not in `table` go
+fn merge(edit: &mut toml_edit::Table, table: &Table) {
+    edit.retain(|key, _| table.contains_key(key));
+
+    for (key, value) in table {
+        match value {
+            Value::Table(table) => {
+                if !edit.get(key).is_some_and(toml_edit::Item::is_table) {
+                    edit.insert(key, toml_edit::table());
+                }
+                let edit = edit[key].as_table_mut().expect("the table was just made sure of.");
+                merge(edit, table);
+            }
+            value => {
+                let new: toml_edit::Value = value.to_string().parse().expect("toml writes valid toml.");
+                match edit.get_mut(key).and_then(toml_edit::Item::as_value_mut) {
+                    Some(old) => {
+                        let decor = old.decor().clone();
+                        *old = new;
+                        *old.decor_mut() = decor;
+                    }
+                    None => {
+                        edit.insert(key, toml_edit::value(new));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Config {
+    pub fn from_document(document: &Table) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        for (name, value) in document {
+            let table = section(name, value)?;
+            match name.as_str() {
+                "window" => {
+                    check_keys(&table, &["width", "height"])?;
+                    config.window_size.width = window_side(&table, "width")?.unwrap_or(config.window_size.width);
+                    config.window_size.height = window_side(&table, "height")?.unwrap_or(config.window_size.height);
+                }
+                "graphics" => {
+                    check_keys(
+                        &table,
+                        &["backend", "power_preference", "adapter", "present_mode", "max_fps", "low_power", "clear_color"],
+                    )?;
+                    if let Some(name) = string(&table, "backend")? {
+                        config.backends = parse_backends(name)?;
+                    }
+                    if let Some(name) = string(&table, "power_preference")? {
+                        config.power_preference = parse_power_preference(name)?;
+                    }
+                    config.adapter = adapter(&table)?;
+                    if let Some(name) = string(&table, "present_mode")? {
+                        config.present_mode = parse_present_mode(name)?;
+                    }
+                    config.max_fps = max_fps(&table)?;
+                    config.low_power = boolean(&table, "low_power")?.unwrap_or(config.low_power);
+
+                    if let Some(value) = table.get("clear_color") {
+                        let table = section("graphics.clear_color", value)?;
+                        check_keys(&table, &["r", "g", "b", "a"])?;
+                        let color = &mut config.clear_color;
+                        for (key, component) in [("r", &mut color.r), ("g", &mut color.g), ("b", &mut color.b), ("a", &mut color.a)] {
+                            *component = color_component(&table, key)?.unwrap_or(*component);
+                        }
+                    }
+                }
+                "log" => {
+                    check_keys(&table, &["level"])?;
+                    if let Some(name) = string(&table, "level")? {
+                        config.log_level = parse_log_level(name)?;
+                    }
+                }
+                "imgui" => {
+                    check_keys(&table, &["ini"])?;
+                    // an empty path turns saving the layout off
+                    config.imgui_ini = string(&table, "ini")?.filter(|path| !path.is_empty()).map(PathBuf::from);
+                }
+                "audio" => config.audio = AudioSelection::from_document(document).map_err(|e| ConfigError(e.0))?,
+                name => {
+                    return Err(ConfigError(format!(
+                        "unknown table [{name}], expected window, graphics, log, imgui or audio."
+                    )))
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn to_document(&self) -> Table {
+        let mut window = Table::new();
+        window.insert("width".into(), Value::Integer(self.window_size.width as i64));
+        window.insert("height".into(), Value::Integer(self.window_size.height as i64));
+
+        let mut graphics = Table::new();
+        graphics.insert("backend".into(), Value::String(name_of(&BACKENDS, &self.backends).to_string()));
+        graphics.insert(
+            "power_preference".into(),
+            Value::String(name_of(&POWER_PREFERENCES, &self.power_preference).to_string()),
+        );
+        match &self.adapter {
+            Some(AdapterChoice::Index(index)) => graphics.insert("adapter".into(), Value::Integer(*index as i64)),
+            Some(choice) => graphics.insert("adapter".into(), Value::String(choice.to_string())),
+            None => None,
+        };
+        graphics.insert("present_mode".into(), Value::String(name_of(&PRESENT_MODES, &self.present_mode).to_string()));
+        if let Some(max_fps) = self.max_fps {
+            graphics.insert("max_fps".into(), Value::Integer(max_fps as i64));
+        }
+        graphics.insert("low_power".into(), Value::Boolean(self.low_power));
+
+        let Color { r, g, b, a } = self.clear_color;
+        let clear_color = [("r", r), ("g", g), ("b", b), ("a", a)]
+            .into_iter()
+            .map(|(key, component)| (key.to_string(), Value::Float(component)))
+            .collect();
+        graphics.insert("clear_color".into(), Value::Table(clear_color));
+
+        let mut log = Table::new();
+        log.insert("level".into(), Value::String(self.log_level.as_str().to_lowercase()));
+
+        let mut imgui = Table::new();
+        let ini = self.imgui_ini.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+        imgui.insert("ini".into(), Value::String(ini));
+
+        let mut document = Table::new();
+        for (name, table) in [("window", window), ("graphics", graphics), ("log", log), ("imgui", imgui)] {
+            document.insert(name.into(), Value::Table(table));
+        }
+        self.audio.write_document(&mut document);
+
+        document
+    }
+
+    /// Errors name the line or the key that is wrong.
+    pub fn parse(source: &str) -> Result<Self, ConfigError> {
+        let document = parse_toml(source).map_err(ConfigError)?;
+        Self::from_document(&document)
+    }
+
+    /// `source` with this config written into it, what's already there keeps its comments.
+    pub fn write_into(&self, source: &str) -> Result<String, ConfigError> {
+        let mut document = source.parse::<DocumentMut>().map_err(|e| ConfigError(e.to_string()))?;
+        merge(document.as_table_mut(), &self.to_document());
+        Ok(document.to_string())
+    }
+
+    /// The defaults if `path` doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(&source).map_err(|e| ConfigError(format!("{}: {e}", path.display()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let error = |e: &dyn fmt::Display| ConfigError(format!("{}: {e}", path.display()));
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(error(&e)),
+        };
+
+        fs::write(path, self.write_into(&source).map_err(|e| error(&e))?).map_err(|e| error(&e))
+    }
+
+    /// What this run uses, the saved config stays as it was.
+    pub fn with_overrides(&self, overrides: &Overrides) -> Self {
+        Self {
+            window_size: overrides.window_size.unwrap_or(self.window_size),
+            backends: overrides.backends.unwrap_or(self.backends),
+            power_preference: overrides.power_preference.unwrap_or(self.power_preference),
+            adapter: overrides.adapter.clone().or_else(|| self.adapter.clone()),
+            log_level: overrides.log_level.unwrap_or(self.log_level),
+            ..self.clone()
+        }
+    }
+
+    pub fn adapter_selection(&self) -> AdapterSelection {
+        AdapterSelection {
+            backends: self.backends,
+            power_preference: self.power_preference,
+            adapter: self.adapter.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        let config = Config::parse("[window]\nwidth = 800\n[graphics.clear_color]\na = 0\n[log]\nlevel = 'DEBUG'").unwrap();
+        assert_eq!(config.window_size, LogicalSize::new(800, 720));
+        assert_eq!(config.clear_color, Color { a: 0.0, ..CLEAR_COLOR });
+        assert_eq!(config.log_level, LevelFilter::Debug);
+        assert_eq!(config.backends, Backends::VULKAN);
+
+        let config = Config::parse("graphics = { clear_color = { r = 1, b = 0.5 } }").unwrap();
+        assert_eq!(config.clear_color, Color { r: 1.0, b: 0.5, ..CLEAR_COLOR });
+    }
+
+    #[test]
+    fn round_trips() {
+        let config = Config {
+            window_size: LogicalSize::new(1920, 1080),
+            backends: Backends::all(),
+            power_preference: PowerPreference::LowPower,
+            adapter: Some(AdapterChoice::Name("Radeon".into())),
+            present_mode: PresentMode::Mailbox,
+            max_fps: Some(144),
+            low_power: true,
+            clear_color: Color { r: 0.0, g: 0.5, b: 1.0, a: 1.0 },
+            log_level: LevelFilter::Warn,
+            imgui_ini: Some(PathBuf::from("layout.ini")),
+            audio: AudioSelection { device: Some("hw:0".into()), sample_rate: Some(44_100), ..Default::default() },
+        };
+
+        assert_eq!(Config::parse(&config.to_document().to_string()).unwrap(), config);
+        assert_eq!(Config::parse(&Config::default().to_document().to_string()).unwrap(), Config::default());
+        let config = Config { adapter: Some(AdapterChoice::Index(1)), ..config };
+        assert_eq!(Config::parse(&config.to_document().to_string()).unwrap(), config);
+        let config = Config { adapter: Some(AdapterChoice::Software), ..config };
+        assert_eq!(Config::parse(&config.to_document().to_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn explains_invalid_values() {
+        let error = |source: &str| Config::parse(source).unwrap_err().0;
+
+        assert!(error("[window]\nwidth = 0").contains("window.width should be an integer from 1 to 16384, got integer 0"));
+        assert!(error("[window]\nheight = \"big\"").contains("window.height"));
+        assert!(error("[graphics]\nbackend = \"glide\"").contains("unknown backend glide, expected one of vulkan, gl"));
+        assert!(error("[graphics]\npower_preference = \"max\"").contains("low_power"));
+        assert!(error("[graphics]\nmax_fps = 0").contains("graphics.max_fps should be an integer from 1 to 1000"));
+        assert!(error("[graphics]\npresent_mode = \"vsync\"").contains("fifo, mailbox, immediate, auto_vsync"));
+        assert!(error("[graphics]\nlow_power = 1").contains("graphics.low_power should be true or false"));
+        assert!(error("[graphics]\nadapter = -1").contains("graphics.adapter should be an adapter index or name"));
+        assert!(error("[graphics.clear_color]\nr = 2.0").contains("graphics.clear_color.r should be a number from 0 to 1"));
+        assert!(error("[log]\nlevel = \"loud\"").contains("off, error, warn, info, debug, trace"));
+        assert!(error("[window]\nfullscreen = true").contains("unknown key fullscreen in [window]"));
+        assert!(error("[shaders]").contains("unknown table [shaders]"));
+        assert!(error("[audio]\nsample_rate = 0").contains("audio.sample_rate"));
+        assert!(error("[graphics]\nclear_color = [1, 0, 0]").contains("graphics.clear_color should be a table, got array"));
+        assert!(error("[window]\nwidth = ").starts_with("line 2"));
+        assert_eq!(error("[log]\nlevel = 'info'\nlevel = 'warn'"), "line 3: duplicate key `level` in table `log`.");
+    }
+
+    #[test]
+    fn saving_keeps_comments() {
+        let source = "# mine\n[window]\n# wide\nwidth = 800 # for now\nheight = 600\n\n[graphics]\nmax_fps = 60\n";
+        let config = Config { window_size: LogicalSize::new(1024, 600), ..Config::default() };
+
+        let saved = config.write_into(source).unwrap();
+        assert!(saved.starts_with("# mine\n[window]\n# wide\nwidth = 1024 # for now\nheight = 600\n"));
+        // unset keys go, new ones are added
+        assert!(!saved.contains("max_fps") && saved.contains("[graphics.clear_color]"));
+        assert_eq!(Config::parse(&saved).unwrap(), config);
+    }
+
+    #[test]
+    fn overrides_apply_without_saving() {
+        let config = Config::default();
+        let overrides = Overrides {
+            backends: Some(Backends::GL),
+            log_level: Some(LevelFilter::Trace),
+            ..Default::default()
+        };
+
+        let run = config.with_overrides(&overrides);
+        assert_eq!((run.backends, run.log_level), (Backends::GL, LevelFilter::Trace));
+        assert_eq!(run.window_size, config.window_size);
+        assert_eq!(config.backends, Backends::VULKAN);
+
+        let config = Config::parse("graphics = { clear_color = { r = 1, b = 0.5 } }").unwrap();
+        assert_eq!(config.clear_color, Color { r: 1.0, b: 0.5, ..CLEAR_COLOR });
+    }
+}
@@ -0,0 +1,230 @@
+use crate::audio::{AudioAnalysis, AudioSource};
+use crate::audio_file::Clip;
+use crate::offscreen::{save_png, Offscreen, OFFSCREEN_FORMAT};
+use crate::pipelines::PipelineKind;
+use crate::playback::Playback;
+use crate::scene::{Scene, SceneAssets};
+use crate::shadertoy::ShadertoyInputs;
+use crate::wgpu_core::{self, AdapterSelection};
+use log::info;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use winit::dpi::PhysicalSize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// numbered pngs in a directory
+    PngSequence,
+    /// uncompressed 4:4:4 yuv stream in a single file
+    Y4m,
+}
+
+impl ExportFormat {
+    /// `.y4m` outputs a stream, anything else is a directory for the png sequence.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("y4m") => ExportFormat::Y4m,
+            _ => ExportFormat::PngSequence,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    pub pipeline_kind: PipelineKind,
+    pub size: PhysicalSize<u32>,
+    pub fps: u32,
+    pub frames: u32,
+    pub output: PathBuf,
+    pub assets: SceneAssets,
+    pub adapter: AdapterSelection,
+    /// played in sync with the frames for audio reactive shaders
+    pub audio: Option<PathBuf>,
+}
+
+/// Value of `uniform.time` for a frame, in milliseconds.
+pub fn frame_time(frame: u32, fps: u32) -> u32 {
+    (frame as u64 * 1000 / fps as u64) as u32
+}
+
+// bt.601 limited range, what ffmpeg assumes for untagged sd material
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let cb = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let cr = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+
+    [y.round() as u8, cb.round() as u8, cr.round() as u8]
+}
+
+/// Writes rgba8 frames as a yuv4mpeg2 stream, which ffmpeg reads without extra flags.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    size: PhysicalSize<u32>,
+    planes: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut writer: W, size: PhysicalSize<u32>, fps: u32) -> io::Result<Self> {
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{fps}:1 Ip A1:1 C444 XCOLORRANGE=LIMITED",
+            size.width, size.height
+        )?;
+
+        Ok(Self {
+            writer,
+            size,
+            planes: Vec::with_capacity((size.width * size.height * 3) as usize),
+        })
+    }
+
+    /// `pixels` are tightly packed rgba8 rows, top row first, alpha is ignored.
+    pub fn write_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        let pixel_count = (self.size.width * self.size.height) as usize;
+        if pixels.len() != pixel_count * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected {} bytes per frame, got {}.", pixel_count * 4, pixels.len()),
+            ));
+        }
+
+        self.planes.clear();
+        self.planes.resize(pixel_count * 3, 0);
+        for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+            let [y, cb, cr] = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+            self.planes[i] = y;
+            self.planes[pixel_count + i] = cb;
+            self.planes[pixel_count * 2 + i] = cr;
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.planes)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Renders `settings.frames` frames offscreen with time advancing at exactly `settings.fps`,
+/// the same settings always give the same output.
+pub async fn run_export(settings: &ExportSettings) -> io::Result<()> {
+    let (_instance, _adapter, dev, queue) = wgpu_core::init_headless(&settings.adapter).await.map_err(io::Error::other)?;
+
+    let size = settings.size;
+    let camera = Scene::default_camera(settings.pipeline_kind);
+    let (mut scene, mut uniform) =
+        Scene::with_assets(&dev, settings.pipeline_kind, size, OFFSCREEN_FORMAT, camera.projection(), &settings.assets)
+            .map_err(io::Error::other)?;
+    let offscreen = Offscreen::new(&dev, size);
+
+    let format = ExportFormat::from_path(&settings.output);
+    let mut y4m = match format {
+        ExportFormat::Y4m => {
+            let file = BufWriter::new(File::create(&settings.output)?);
+            Some(Y4mWriter::new(file, size, settings.fps)?)
+        }
+        ExportFormat::PngSequence => {
+            fs::create_dir_all(&settings.output)?;
+            None
+        }
+    };
+
+    // plays from the start of the export, no speakers and no live input
+    let mut audio = match &settings.audio {
+        Some(path) => {
+            let clip = Clip::load(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let mut playback = Playback::new(clip);
+            playback.play();
+            Some(AudioSource::File(playback))
+        }
+        None => None,
+    };
+    let mut audio_analysis = AudioAnalysis::default();
+
+    info!(
+        "exporting {} frames at {}x{}, {} fps to {}.",
+        settings.frames, size.width, size.height, settings.fps, settings.output.display()
+    );
+
+    // the date starts at the epoch as well, so it can't change the output either
+    let mut shadertoy_inputs = ShadertoyInputs::default();
+    for frame in 0..settings.frames {
+        uniform.time = frame_time(frame, settings.fps);
+        camera.write_uniform(&mut uniform, size.width as f32 / size.height as f32);
+        let date = UNIX_EPOCH + Duration::from_millis(uniform.time as u64);
+        shadertoy_inputs.write_uniform(&mut uniform, 1.0 / settings.fps as f32, date);
+        if let Some(audio) = audio.as_mut() {
+            audio.update(uniform.time);
+            audio_analysis.update(audio, 1.0 / settings.fps as f32);
+            audio_analysis.write_uniform(&mut uniform);
+            scene.write_audio(&queue, &audio_analysis.texels());
+        }
+        queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let pixels = offscreen.render_scene(&dev, &queue, &mut scene);
+        match y4m.as_mut() {
+            Some(y4m) => y4m.write_frame(&pixels)?,
+            None => {
+                let path = settings.output.join(format!("frame_{frame:05}.png"));
+                save_png(&path, size, &pixels).map_err(io::Error::other)?;
+            }
+        }
+    }
+
+    if let Some(y4m) = y4m {
+        y4m.finish()?;
+    }
+    info!("export finished.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_is_fixed_per_frame() {
+        assert_eq!(frame_time(0, 60), 0);
+        assert_eq!(frame_time(1, 60), 16);
+        assert_eq!(frame_time(60, 60), 1000);
+        assert_eq!(frame_time(90, 30), 3000);
+        // no drift from accumulating rounded deltas
+        assert_eq!(frame_time(600_000, 60), 10_000_000);
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(ExportFormat::from_path(Path::new("out.y4m")), ExportFormat::Y4m);
+        assert_eq!(ExportFormat::from_path(Path::new("frames")), ExportFormat::PngSequence);
+    }
+
+    #[test]
+    fn y4m_stream_layout() {
+        let size = PhysicalSize::new(2, 1);
+        let mut y4m = Y4mWriter::new(Vec::new(), size, 30).unwrap();
+        y4m.write_frame(&[255, 255, 255, 255, 0, 0, 0, 255]).unwrap();
+        y4m.write_frame(&[255, 0, 0, 255, 0, 0, 255, 0]).unwrap();
+        assert!(y4m.write_frame(&[0; 4]).is_err());
+        let data = y4m.finish().unwrap();
+
+        let header = b"YUV4MPEG2 W2 H1 F30:1 Ip A1:1 C444 XCOLORRANGE=LIMITED\n";
+        assert!(data.starts_with(header));
+
+        let frames = &data[header.len()..];
+        assert_eq!(frames.len(), 2 * (6 + 6));
+        // white and black, planar y, cb, cr
+        assert_eq!(&frames[..12], b"FRAME\n\xEB\x10\x80\x80\x80\x80");
+        // pure red and blue push cr and cb to their maximum
+        let [_, _, cr] = rgb_to_ycbcr(255, 0, 0);
+        let [_, cb, _] = rgb_to_ycbcr(0, 0, 255);
+        assert_eq!((cr, cb), (240, 240));
+        assert_eq!(frames[12 + 6 + 4], 240);
+        assert_eq!(frames[12 + 6 + 3], 240);
+    }
+}
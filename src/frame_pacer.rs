@@ -0,0 +1,132 @@
+//! Decides when the window draws its next frame. A frame limit waits with the event loop for
+//! most of the frame and spins for the rest, timers alone oversleep by up to a millisecond.
+//! Low power mode stops drawing while nothing moves.
+
+use std::time::{Duration, Instant};
+
+/// Spun instead of slept before a frame is due.
+pub const SPIN_MARGIN: Duration = Duration::from_millis(2);
+/// Low power mode keeps drawing this long after input, so the ui can settle.
+pub const INPUT_LINGER: Duration = Duration::from_millis(500);
+/// How often an idle window still wakes up, e.g. to notice edited shaders.
+pub const IDLE_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pace {
+    Redraw,
+    /// nothing to draw before then, events may still wake the loop earlier
+    WaitUntil(Instant),
+}
+
+#[derive(Clone, Debug)]
+pub struct FramePacer {
+    next_frame: Option<Instant>,
+    active_until: Instant,
+    // stopped drawing since `woke` was last asked
+    idled: bool,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self {
+            next_frame: None,
+            active_until: Instant::now() + INPUT_LINGER,
+            idled: false,
+        }
+    }
+}
+
+fn spin_until(deadline: Instant) {
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+impl FramePacer {
+    pub fn input(&mut self, now: Instant) {
+        self.active_until = now + INPUT_LINGER;
+    }
+
+    /// Whether low power mode stopped drawing since the last call, the time since the last
+    /// frame is then no frame time.
+    pub fn woke(&mut self) -> bool {
+        std::mem::take(&mut self.idled)
+    }
+
+    /// Called when the event loop is about to wait. `animating` keeps a low power window drawing,
+    /// e.g. while the camera moves.
+    pub fn pace(&mut self, now: Instant, max_fps: Option<u32>, low_power: bool, animating: bool) -> Pace {
+        let (pace, spin) = self.schedule(now, max_fps, low_power, animating);
+        if let Some(deadline) = spin {
+            spin_until(deadline);
+        }
+
+        pace
+    }
+
+    // `pace` without the spinning, also returns what to spin until before drawing
+    fn schedule(&mut self, now: Instant, max_fps: Option<u32>, low_power: bool, animating: bool) -> (Pace, Option<Instant>) {
+        if low_power && !animating && now >= self.active_until {
+            // the first frame after idling is drawn right away
+            self.next_frame = None;
+            self.idled = true;
+            return (Pace::WaitUntil(now + IDLE_POLL), None);
+        }
+
+        let Some(max_fps) = max_fps else {
+            self.next_frame = None;
+            return (Pace::Redraw, None);
+        };
+
+        let deadline = self.next_frame.unwrap_or(now);
+        if deadline > now + SPIN_MARGIN {
+            return (Pace::WaitUntil(deadline - SPIN_MARGIN), None);
+        }
+
+        // counting from the deadline keeps the average rate exact, unless a whole frame was missed
+        let period = Duration::from_secs_f64(1.0 / max_fps.max(1) as f64);
+        self.next_frame = Some(if deadline + period < now { now + period } else { deadline + period });
+
+        (Pace::Redraw, (deadline > now).then_some(deadline))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_frame_rate() {
+        let mut pacer = FramePacer::default();
+        let start = Instant::now();
+        let period = Duration::from_secs_f64(1.0 / 50.0);
+        assert_eq!(pacer.schedule(start, None, false, false), (Pace::Redraw, None));
+        assert_eq!(pacer.schedule(start, Some(50), false, false), (Pace::Redraw, None));
+
+        let due = start + period;
+        assert_eq!(pacer.schedule(start, Some(50), false, false), (Pace::WaitUntil(due - SPIN_MARGIN), None));
+
+        // spins through the margin and draws on time
+        assert_eq!(pacer.schedule(due - SPIN_MARGIN, Some(50), false, false), (Pace::Redraw, Some(due)));
+        assert_eq!(pacer.next_frame, Some(due + period));
+
+        // after missing a whole frame it counts from when the late one is drawn
+        let late = due + period * 3;
+        assert_eq!(pacer.schedule(late, Some(50), false, false), (Pace::Redraw, None));
+        assert_eq!(pacer.next_frame, Some(late + period));
+    }
+
+    #[test]
+    fn low_power_waits_for_input() {
+        let mut pacer = FramePacer::default();
+        let idle = Instant::now() + INPUT_LINGER;
+        assert!(!pacer.woke());
+        assert_eq!(pacer.pace(idle, None, true, false), Pace::WaitUntil(idle + IDLE_POLL));
+        assert_eq!(pacer.pace(idle, None, true, true), Pace::Redraw);
+        assert!(pacer.woke() && !pacer.woke());
+
+        pacer.input(idle);
+        assert_eq!(pacer.pace(idle, None, true, false), Pace::Redraw);
+        assert_eq!(pacer.pace(idle + INPUT_LINGER, None, false, false), Pace::Redraw);
+    }
+}
@@ -0,0 +1,118 @@
+//! Frame time statistics for the info window and csv logs of raw frame times to dig into
+//! elsewhere. All times are in milliseconds.
+
+use crate::screenshot::timestamp;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Frames the statistics and the graph look back on.
+pub const HISTORY_FRAMES: usize = 2000;
+/// A frame taking this many times the median is a stutter.
+pub const STUTTER_FACTOR: f32 = 2.0;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub frames: usize,
+    pub min: f32,
+    pub mean: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    /// fps over the slowest 1% and 0.1% of frames
+    pub low_1: f32,
+    pub low_01: f32,
+    pub stutters: usize,
+}
+
+fn mean(times: &[f32]) -> f32 {
+    times.iter().sum::<f32>() / times.len() as f32
+}
+
+// nearest rank, `sorted` is never empty
+fn percentile(sorted: &[f32], percent: f32) -> f32 {
+    let rank = (sorted.len() as f32 * percent / 100.0).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn low(sorted: &[f32], fraction: f32) -> f32 {
+    let count = ((sorted.len() as f32 * fraction).ceil() as usize).max(1);
+    1000.0 / mean(&sorted[sorted.len() - count..])
+}
+
+impl FrameStats {
+    pub fn from_frame_times(times: &[f32]) -> Self {
+        if times.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = times.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let p50 = percentile(&sorted, 50.0);
+
+        Self {
+            frames: sorted.len(),
+            min: sorted[0],
+            mean: mean(&sorted),
+            max: sorted[sorted.len() - 1],
+            p50,
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            low_1: low(&sorted, 0.01),
+            low_01: low(&sorted, 0.001),
+            stutters: sorted.iter().filter(|&&time| time > p50 * STUTTER_FACTOR).count(),
+        }
+    }
+}
+
+/// `frame,time_ms,frame_time_ms`, `time_ms` is when the frame ended counted from the first one's start.
+pub fn write_csv(mut writer: impl Write, times: &[f32]) -> io::Result<()> {
+    writeln!(writer, "frame,time_ms,frame_time_ms")?;
+
+    let mut time = 0.0;
+    for (frame, frame_time) in times.iter().enumerate() {
+        time += frame_time;
+        writeln!(writer, "{frame},{time:.3},{frame_time:.3}")?;
+    }
+
+    writer.flush()
+}
+
+/// Writes `frametimes_<utc date>_<utc time>.csv` to `dir`.
+pub fn save_csv(dir: &Path, times: &[f32]) -> io::Result<PathBuf> {
+    let path = dir.join(format!("frametimes_{}.csv", timestamp(SystemTime::now())));
+    write_csv(BufWriter::new(File::create(&path)?), times)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_frame_times() {
+        // 995 smooth frames and 5 hitches
+        let mut times = vec![10.0; 995];
+        times.extend([25.0, 40.0, 20.0, 50.0, 21.0]);
+        let stats = FrameStats::from_frame_times(&times);
+
+        assert_eq!((stats.frames, stats.min, stats.max, stats.p50, stats.p99), (1000, 10.0, 50.0, 10.0, 10.0));
+        assert!((stats.mean - 10.106).abs() < 1e-3);
+        // slowest 10 frames average 20.6 ms, the slowest one 50 ms
+        assert!((stats.low_1 - 1000.0 / 20.6).abs() < 1e-2);
+        assert_eq!(stats.low_01, 20.0);
+        assert_eq!(stats.stutters, 4);
+
+        assert_eq!(FrameStats::from_frame_times(&[]), FrameStats::default());
+    }
+
+    #[test]
+    fn writes_csv() {
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &[16.5, 17.25]).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "frame,time_ms,frame_time_ms\n0,16.500,16.500\n1,33.750,17.250\n");
+    }
+}
@@ -0,0 +1,180 @@
+//! Rebuilds the scene pipeline when its shader sources change on disk,
+//! so shaders can be edited while the window is open.
+
+use crate::pipelines::{PipelineError, PipelineKind};
+use crate::scene::Scene;
+use crate::shader_loader::ShaderOrigin;
+use crate::shadertoy_project::Project;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use wgpu::Device;
+
+/// Checking a few modification times is cheap, but there's no need to do it every frame.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+fn modified(root: &Path, path: &str) -> Option<SystemTime> {
+    fs::metadata(root.join(path)).and_then(|m| m.modified()).ok()
+}
+
+/// Polls the modification times of a set of files below `root`.
+pub struct ShaderWatcher {
+    root: PathBuf,
+    files: Vec<(String, Option<SystemTime>)>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    /// `paths` are relative to `root`.
+    pub fn new(root: PathBuf, paths: Vec<String>, interval: Duration) -> Self {
+        let mut watcher = Self {
+            root,
+            files: Vec::new(),
+            interval,
+            last_poll: Instant::now(),
+        };
+        watcher.set_paths(paths);
+
+        watcher
+    }
+
+    /// Replaces the watched files, the current state of the new ones counts as unchanged.
+    pub fn set_paths(&mut self, paths: Vec<String>) {
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&self.root, &path);
+                (path, modified)
+            })
+            .collect();
+    }
+
+    /// Files that were saved, removed or recreated since the last poll, always empty
+    /// until `interval` has passed.
+    pub fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let root = &self.root;
+        self.files
+            .iter_mut()
+            .filter_map(|(path, last_modified)| {
+                let modified = modified(root, path);
+                (modified != *last_modified).then(|| {
+                    *last_modified = modified;
+                    path.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Watches the shaders of one scene and keeps the last error around for the ui.
+pub struct HotReload {
+    watcher: ShaderWatcher,
+    origin: ShaderOrigin,
+    pub error: Option<PipelineError>,
+}
+
+impl HotReload {
+    /// Watches the directory the scene read its shaders from, or the sources the binary was
+    /// built from if they are still around. `None` if neither has the files.
+    pub fn new(scene: &Scene) -> Option<Self> {
+        let root = match scene.shader_origin() {
+            ShaderOrigin::Disk(root) => root.clone(),
+            ShaderOrigin::Embedded => PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+        };
+        let paths = scene.shader_paths();
+        if !paths.iter().all(|path| root.join(path).is_file()) {
+            return None;
+        }
+
+        info!("watching {} for shader changes.", root.join("src/shader").display());
+
+        Some(Self {
+            watcher: ShaderWatcher::new(root.clone(), paths, POLL_INTERVAL),
+            origin: ShaderOrigin::Disk(root),
+            error: None,
+        })
+    }
+
+    /// Rebuilds the pipeline of `scene` if one of its shaders changed, returns whether one did.
+    pub fn update(&mut self, dev: &Device, scene: &mut Scene) -> bool {
+        let changed = self.watcher.poll();
+        if changed.is_empty() {
+            return false;
+        }
+
+        for path in &changed {
+            info!("{} changed, rebuilding the {:?} pipeline.", path, scene.kind);
+        }
+
+        match scene.reload_pipeline(dev, &self.origin) {
+            Ok(()) => {
+                self.error = None;
+                // a shadertoy project may have gained or lost passes
+                self.watcher.set_paths(scene.shader_paths());
+            }
+            Err(e) => {
+                error!("keeping the last pipeline: {}", e);
+                self.error = Some(e);
+
+                // passes that were just added have to be watched before they build
+                if scene.kind == PipelineKind::Shadertoy {
+                    if let Ok(project) = Project::load(&self.origin) {
+                        let mut paths = scene.shader_paths();
+                        paths.extend(project.files());
+                        paths.sort();
+                        paths.dedup();
+                        self.watcher.set_paths(paths);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::process;
+
+    const FRAG: &str = "watched.frag";
+    const WGSL: &str = "watched.wgsl";
+
+    #[test]
+    fn reports_changed_files() {
+        let root = std::env::temp_dir().join(format!("patibu_watcher_{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(FRAG), "").unwrap();
+        fs::write(root.join(WGSL), "").unwrap();
+
+        let mut watcher = ShaderWatcher::new(root.clone(), vec![FRAG.into(), WGSL.into()], Duration::ZERO);
+        assert!(watcher.poll().is_empty());
+
+        // set explicitly, the file system may not tell apart two writes in quick succession
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options().write(true).open(root.join(FRAG)).unwrap().set_modified(later).unwrap();
+        assert_eq!(watcher.poll(), [FRAG]);
+        assert!(watcher.poll().is_empty());
+
+        fs::remove_file(root.join(WGSL)).unwrap();
+        assert_eq!(watcher.poll(), [WGSL]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn waits_for_interval() {
+        let mut watcher = ShaderWatcher::new(PathBuf::from("missing"), vec![FRAG.into()], Duration::from_secs(3600));
+        watcher.files[0].1 = Some(SystemTime::UNIX_EPOCH);
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -1,10 +1,21 @@
+use crate::audio::{buffer_sizes, channel_counts, sample_rates, Audio};
+use crate::config::{Config, LOG_LEVELS, PRESENT_MODES};
+use crate::frame_stats::save_csv;
+use crate::pipelines::PipelineError;
+use crate::playback::Playback;
+use crate::screenshot::ScreenshotRequest;
+use crate::wgpu_core::FrameInfo;
 use imgui::{Condition, Context, FontSource};
 use imgui_wgpu::{Renderer, RendererConfig};
 use imgui_winit_support::WinitPlatform;
-use wgpu::{CommandEncoder, Device, LoadOp, Operations, Queue, RenderPassColorAttachment, RenderPassDescriptor, StoreOp, SurfaceConfiguration, TextureView};
+use log::{error, info};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use wgpu::{CommandEncoder, Device, LoadOp, Operations, Queue, RenderPassColorAttachment, RenderPassDescriptor, StoreOp, SurfaceConfiguration, TextureFormat, TextureView};
 use winit::window::Window;
 
-pub fn setup_imgui(window: &Window, dev: &Device, queue: &Queue, surf_cfg: &SurfaceConfiguration, hidpi_factor: f64) -> (Context, WinitPlatform, Renderer) {
+/// `ini` is where window positions are kept between runs, `None` doesn't keep them.
+pub fn setup_imgui(window: &Window, dev: &Device, queue: &Queue, surf_cfg: &SurfaceConfiguration, hidpi_factor: f64, ini: Option<PathBuf>) -> (Context, WinitPlatform, Renderer) {
     let mut imgui = Context::create();
     let mut platform = WinitPlatform::init(&mut imgui);
     platform.attach_window(
@@ -12,7 +23,7 @@ pub fn setup_imgui(window: &Window, dev: &Device, queue: &Queue, surf_cfg: &Surf
         &window,
         imgui_winit_support::HiDpiMode::Default,
     );
-    imgui.set_ini_filename(None);
+    imgui.set_ini_filename(ini);
 
     let font_size = (13.0 * hidpi_factor) as f32;
     imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
@@ -26,29 +37,244 @@ pub fn setup_imgui(window: &Window, dev: &Device, queue: &Queue, surf_cfg: &Surf
         }),
     }]);
 
+    let renderer = create_renderer(&mut imgui, dev, queue, surf_cfg.format);
+
+    (imgui, platform, renderer)
+}
+
+/// Also uploads the font atlas, called again when the device is recreated.
+pub fn create_renderer(imgui: &mut Context, dev: &Device, queue: &Queue, format: TextureFormat) -> Renderer {
     let renderer_cfg = RendererConfig {
-        texture_format: surf_cfg.format,
+        texture_format: format,
         ..Default::default()
     };
 
-    let renderer = Renderer::new(&mut imgui, &dev, &queue, renderer_cfg);
-
-    (imgui, platform, renderer)
+    Renderer::new(imgui, dev, queue, renderer_cfg)
 }
 
-pub fn base_ui(ui: &imgui::Ui, frame_time: f32, fps: f32) {
-    ui.window("info").size([400.0, 200.0], Condition::FirstUseEver).position([10.0, 10.0], Condition::FirstUseEver).build(|| {
-        ui.text(format!("frame_time: {frame_time}"));
-        ui.text(format!("fps: {fps}"));
+/// Most recent frames in the frame time graph.
+const PLOTTED_FRAMES: usize = 240;
+
+pub fn base_ui(ui: &imgui::Ui, frame_info: &mut FrameInfo, screenshot: &mut ScreenshotRequest) {
+    ui.window("info").size([400.0, 330.0], Condition::FirstUseEver).position([10.0, 10.0], Condition::FirstUseEver).build(|| {
+        ui.text(format!("frame_time: {}", frame_info.frame_time));
+        ui.text(format!("fps: {}", frame_info.fps));
 
         let mouse_pos = ui.io().mouse_pos;
         ui.text(format!(
             "mouse_pos: ({:.1},{:.1})",
             mouse_pos[0], mouse_pos[1]
         ));
+
+        ui.separator();
+        if ui.button("screenshot (F2)") {
+            screenshot.pending = true;
+        }
+        ui.same_line();
+        ui.checkbox("include overlay", &mut screenshot.include_overlay);
+
+        ui.separator();
+        let stats = &frame_info.stats;
+        ui.text(format!("min {:.2} / mean {:.2} / max {:.2} ms", stats.min, stats.mean, stats.max));
+        ui.text(format!("p50 {:.2} / p95 {:.2} / p99 {:.2} ms", stats.p50, stats.p95, stats.p99));
+        ui.text(format!("1% low {:.1} fps, 0.1% low {:.1} fps", stats.low_1, stats.low_01));
+        ui.text(format!("stutters: {} of {} frames", stats.stutters, stats.frames));
+
+        // a fixed scale keeps spikes from squashing the rest of the graph
+        let scale_max = (stats.p99 * 2.0).max(1.0);
+        let history = frame_info.history.make_contiguous();
+        let shown = &history[history.len().saturating_sub(PLOTTED_FRAMES)..];
+        ui.plot_lines("##frame times", shown)
+            .graph_size([0.0, 60.0])
+            .scale_min(0.0)
+            .scale_max(scale_max)
+            .overlay_text(format!("frame time, 0 to {scale_max:.1} ms"))
+            .build();
+
+        let label = match &frame_info.recording {
+            Some(times) => format!("save {} frame times", times.len()),
+            None => "record frame times".to_string(),
+        };
+        if ui.button(label) {
+            match frame_info.recording.take() {
+                Some(times) => match save_csv(Path::new("."), &times) {
+                    Ok(path) => info!("saved frame times {}.", path.display()),
+                    Err(e) => error!("failed to save frame times: {}", e),
+                },
+                None => frame_info.recording = Some(Vec::new()),
+            }
+        }
     });
 }
 
+/// What "limit fps" starts at.
+const DEFAULT_MAX_FPS: u32 = 60;
+
+/// Edits the parts of `config` that apply right away, returns true once an edit is done
+/// and the config should be saved.
+pub fn settings_ui(ui: &imgui::Ui, config: &mut Config) -> bool {
+    let mut done = false;
+
+    ui.window("settings").size([400.0, 160.0], Condition::FirstUseEver).position([420.0, 220.0], Condition::FirstUseEver).build(|| {
+        let color = config.clear_color;
+        let mut rgba = [color.r, color.g, color.b, color.a].map(|c| c as f32);
+        if ui.color_edit4("clear color", &mut rgba) {
+            let [r, g, b, a] = rgba.map(f64::from);
+            config.clear_color = wgpu::Color { r, g, b, a };
+        }
+        // dragging edits every frame, saving waits for the release
+        done |= ui.is_item_deactivated_after_edit();
+
+        let levels: Vec<String> = LOG_LEVELS.iter().map(|level| level.as_str().to_lowercase()).collect();
+        let mut index = LOG_LEVELS.iter().position(|&level| level == config.log_level).unwrap_or(0);
+        if ui.combo_simple_string("log level", &mut index, &levels) {
+            config.log_level = LOG_LEVELS[index];
+            done = true;
+        }
+
+        ui.separator();
+        let modes: Vec<&str> = PRESENT_MODES.iter().map(|(name, _)| *name).collect();
+        let mut index = PRESENT_MODES.iter().position(|&(_, mode)| mode == config.present_mode).unwrap_or(0);
+        if ui.combo_simple_string("present mode", &mut index, &modes) {
+            config.present_mode = PRESENT_MODES[index].1;
+            done = true;
+        }
+
+        let mut limit = config.max_fps.is_some();
+        if ui.checkbox("limit fps", &mut limit) {
+            config.max_fps = limit.then_some(DEFAULT_MAX_FPS);
+            done = true;
+        }
+        if let Some(max_fps) = config.max_fps.as_mut() {
+            ui.same_line();
+            ui.slider("##max fps", 10, 360, max_fps);
+            done |= ui.is_item_deactivated_after_edit();
+        }
+
+        done |= ui.checkbox("low power", &mut config.low_power);
+        if ui.is_item_hovered() {
+            ui.tooltip_text("only redraw on input and while the picture changes on its own, e.g. the camera moves, the shader runs on the time or audio comes in.");
+        }
+    });
+
+    done
+}
+
+/// Shown while the edited shaders don't build, the scene keeps its last working pipeline.
+pub fn shader_error_ui(ui: &imgui::Ui, error: &PipelineError) {
+    ui.window("shader error").size([600.0, 200.0], Condition::FirstUseEver).position([10.0, 220.0], Condition::FirstUseEver).build(|| {
+        ui.text_colored([1.0, 0.4, 0.4, 1.0], "rendering with the last working pipeline.");
+        ui.separator();
+        ui.text_wrapped(error.to_string());
+    });
+}
+
+/// Transport for the clip given with `--audio`.
+pub fn playback_ui(ui: &imgui::Ui, playback: &mut Playback) {
+    ui.window("playback").size([400.0, 110.0], Condition::FirstUseEver).position([10.0, 430.0], Condition::FirstUseEver).build(|| {
+        let label = if playback.is_playing() { "pause" } else { "play" };
+        if ui.button(label) {
+            if playback.is_playing() {
+                playback.pause();
+            } else {
+                playback.play();
+            }
+        }
+        ui.same_line();
+        let mut looping = playback.is_looping();
+        if ui.checkbox("loop", &mut looping) {
+            playback.set_looping(looping);
+        }
+
+        let duration = playback.clip().duration() as f32;
+        let mut position = playback.position() as f32;
+        if ui.slider_config("position", 0.0, duration).display_format("%.1f s").build(&mut position) {
+            playback.seek(position as f64);
+        }
+        ui.text(format!("{:.1} / {:.1} s", playback.position(), duration));
+    });
+}
+
+// `None` is listed as "default", a value that isn't among `options` is kept and listed as well
+fn option_combo<T: Clone + PartialEq + Display>(ui: &imgui::Ui, label: &str, value: &mut Option<T>, options: &[T]) -> bool {
+    let mut options = options.to_vec();
+    if let Some(current) = value.as_ref().filter(|v| !options.contains(v)) {
+        options.push(current.clone());
+    }
+
+    let items: Vec<String> = std::iter::once("default".to_string())
+        .chain(options.iter().map(|option| option.to_string()))
+        .collect();
+    let mut index = value.as_ref().and_then(|v| options.iter().position(|o| o == v)).map_or(0, |i| i + 1);
+    if !ui.combo_simple_string(label, &mut index, &items) {
+        return false;
+    }
+
+    let chosen = index.checked_sub(1).map(|i| options[i].clone());
+    let changed = chosen != *value;
+    *value = chosen;
+
+    changed
+}
+
+/// Input device choice, returns true when the selection changed so it can be saved.
+pub fn audio_device_ui(ui: &imgui::Ui, audio: &mut Audio) -> bool {
+    let mut selection = audio.selection().clone();
+    let mut changed = false;
+    let mut refresh = false;
+
+    ui.window("audio input").size([400.0, 200.0], Condition::FirstUseEver).position([420.0, 10.0], Condition::FirstUseEver).build(|| {
+        match (audio.info(), audio.error()) {
+            (Some(info), _) => ui.text_wrapped(info.to_string()),
+            (None, Some(e)) => ui.text_colored([1.0, 0.4, 0.4, 1.0], format!("no input: {e}")),
+            (None, None) => ui.text("no input."),
+        }
+        ui.separator();
+
+        let hosts = audio.devices();
+        let host_names: Vec<String> = hosts.iter().map(|host| host.name.clone()).collect();
+        let host_changed = option_combo(ui, "host", &mut selection.host, &host_names);
+        if host_changed {
+            selection.device = None;
+        }
+
+        let host = hosts.iter().find(|host| match &selection.host {
+            Some(name) => host.name.eq_ignore_ascii_case(name),
+            None => host.is_default,
+        });
+        let devices = host.map_or(&[][..], |host| &host.devices[..]);
+        let device_names: Vec<String> = devices.iter().map(|device| device.name.clone()).collect();
+        // the configs of one device rarely fit another
+        let device_changed = option_combo(ui, "device", &mut selection.device, &device_names) | host_changed;
+        if device_changed {
+            selection.sample_rate = None;
+            selection.channels = None;
+            selection.buffer_size = None;
+        }
+        changed |= device_changed;
+
+        let device = devices.iter().find(|device| match &selection.device {
+            Some(name) => &device.name == name,
+            None => device.is_default,
+        });
+        let configs = device.map_or(&[][..], |device| &device.configs[..]);
+        changed |= option_combo(ui, "sample rate", &mut selection.sample_rate, &sample_rates(configs));
+        changed |= option_combo(ui, "channels", &mut selection.channels, &channel_counts(configs));
+        changed |= option_combo(ui, "buffer size", &mut selection.buffer_size, &buffer_sizes(configs));
+
+        refresh = ui.button("refresh devices");
+    });
+
+    if refresh {
+        audio.refresh_devices();
+    }
+    if changed {
+        audio.select(selection);
+    }
+
+    changed
+}
+
 pub fn imgui_render_pass(dev: &Device, queue: &Queue, encoder: &mut CommandEncoder, imgui: &mut Context, renderer: &mut Renderer, view: &TextureView) {
     let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
         label: Some("imgui_render_pass"),
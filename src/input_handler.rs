@@ -1,3 +1,5 @@
+use crate::camera::Camera;
+use crate::screenshot::ScreenshotRequest;
 use crate::uniform::Uniform;
 use wgpu::{Buffer, Queue};
 use winit::event::{ElementState, KeyEvent};
@@ -6,6 +8,7 @@ use winit::keyboard::{Key, NamedKey};
 use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 use winit::window::{Fullscreen, Window};
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_keyboard(
     event: &KeyEvent,
     elwt: &EventLoopWindowTarget<()>,
@@ -13,12 +16,25 @@ pub fn handle_keyboard(
     queue: &Queue,
     uniform: &mut Uniform,
     uniform_buffer: &Buffer,
+    camera: &mut Camera,
+    screenshot: &mut ScreenshotRequest,
+    ui_wants_keyboard: bool,
 ) {
+    // camera keys are tracked on release too, releases always go through so no key gets stuck
+    // while typing into the ui
+    let released = event.state == ElementState::Released;
+    if (!ui_wants_keyboard || released) && camera.handle_key(event) {
+        return;
+    }
+
     if event.state == ElementState::Pressed {
         match event.key_without_modifiers().as_ref() {
             Key::Named(NamedKey::F12) => {
                 elwt.exit();
             },
+            Key::Named(NamedKey::F2) => {
+                screenshot.pending = true;
+            }
             Key::Named(NamedKey::F11) => {
                 if window.fullscreen().is_some() {
                     window.set_fullscreen(None);
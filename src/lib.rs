@@ -0,0 +1,31 @@
+pub mod audio;
+pub mod audio_file;
+pub mod beat;
+pub mod bench;
+pub mod camera;
+pub mod cli;
+pub mod config;
+pub mod export;
+pub mod frame_pacer;
+pub mod frame_stats;
+pub mod hot_reload;
+pub mod imgui_handler;
+pub mod input_handler;
+pub mod multipass;
+pub mod offscreen;
+pub mod pipelines;
+pub mod playback;
+pub mod projection;
+pub mod ring_buffer;
+pub mod scene;
+pub mod screenshot;
+pub mod shader_loader;
+pub mod shadertoy;
+pub mod shadertoy_project;
+pub mod spectrum;
+pub mod streaming;
+pub mod svo;
+pub mod uniform;
+pub mod vertex;
+pub mod vox;
+pub mod wgpu_core;
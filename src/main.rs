@@ -1,207 +1,360 @@
-mod audio;
-mod imgui_handler;
-mod input_handler;
-mod pipelines;
-mod streaming;
-mod uniform;
-mod vertex;
-mod wgpu_core;
-
-use crate::imgui_handler::{base_ui, imgui_render_pass, setup_imgui};
-use crate::input_handler::{handle_keyboard};
-use crate::pipelines::{create_depth_texture, create_main_pipeline};
-use crate::streaming::{create_buffer_descriptors, create_uniform_buffer};
-use crate::vertex::{Vertex, CUBE_INDICES, CUBE_UV_COORDS, CUBE_VERTEX_POSITIONS};
-use crate::wgpu_core::FrameInfo;
-use ansi_term::Color::{Blue, Red, Yellow};
+use patibu::audio::{self, Audio, AudioAnalysis, AudioSource};
+use patibu::bench::{run_bench, BenchSettings};
+use patibu::cli::{self, Cli, Command, SceneArgs};
+use patibu::audio_file::Clip;
+use patibu::config::{Config, Overrides};
+use patibu::export::{run_export, ExportSettings};
+use patibu::frame_pacer::{FramePacer, Pace};
+use patibu::hot_reload::HotReload;
+use patibu::imgui_handler::{audio_device_ui, base_ui, create_renderer, imgui_render_pass, playback_ui, settings_ui, setup_imgui, shader_error_ui};
+use patibu::input_handler::{handle_keyboard};
+use patibu::offscreen::{save_png, Offscreen, OFFSCREEN_FORMAT};
+use patibu::pipelines::{create_depth_texture, PipelineKind};
+use patibu::playback::Playback;
+use patibu::scene::{Scene, SceneAssets};
+use patibu::screenshot::{Capture, ScreenshotRequest};
+use patibu::shader_loader::ShaderOrigin;
+use patibu::shadertoy::ShadertoyInputs;
+use patibu::svo::Octree;
+use patibu::vox::VoxModel;
+use patibu::wgpu_core::{self, describe_adapter, AdapterError, AdapterSelection, FrameInfo};
+use ansi_term::Color::{Blue, Purple, Red, Yellow};
 use ansi_term::Style;
 use env_logger::{Builder, Target};
-use log::{info, LevelFilter};
+use log::{debug, error, info, warn, LevelFilter};
 use pollster::block_on;
 use std::io::Write;
-use std::{env, io};
-use glam::Vec2;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io};
+use wgpu::{Backends, SurfaceError};
 use winit::{
-    dpi::LogicalSize,
-    event::{Event, WindowEvent},
+    dpi::PhysicalSize,
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
     window::WindowBuilder,
 };
-use crate::audio::Audio;
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
+/// `config` is what's saved to `config_path` when the ui changes it, `overrides` only apply to this run.
+async fn run(
+    event_loop: EventLoop<()>,
+    window: Window,
+    scene_args: SceneArgs,
+    audio_file: Option<PathBuf>,
+    mut config: Config,
+    config_path: PathBuf,
+    overrides: Overrides,
+) {
     //
     // wgpu core
     //
-    let (mut size, instance, surf, hidpi_factor, adapter, dev, queue) =
-        wgpu_core::init_wgpu(&window).await;
+    let startup = config.with_overrides(&overrides);
+    let pipeline_kind = scene_args.pipeline_kind;
+    let (mut size, instance, surf, hidpi_factor, adapter, mut dev, mut queue) =
+        exit_on_error(wgpu_core::init_wgpu(&window, &startup.adapter_selection()).await);
+    let mut device_lost = wgpu_core::watch_device(&dev);
     //
-    // create buffers
+    // scene, buffers and pipelines
     //
-    let (mut uniform, uniform_buffer) = create_uniform_buffer(&dev, size);
-    uniform.update_proj(Vec2::new(size.width as f32, size.height as f32));
-    let (vertex_buffer, index_buffer, num_indices) = streaming::create_polygon_buffers(
-        &dev,
-        &CUBE_VERTEX_POSITIONS
-            .iter()
-            .enumerate()
-            .map(|(i, &pos)| Vertex::new(pos, CUBE_UV_COORDS[i]))
-            .collect::<Vec<Vertex>>(),
-        &Vec::from(CUBE_INDICES),
-    );
+    let mut camera = Scene::default_camera(pipeline_kind);
+
+    let swapchain_capabilities = surf.get_capabilities(&adapter);
+    // shadertoy output is decoded for srgb targets, see shader/shadertoy/main.glsl
+    let swapchain_format = swapchain_capabilities
+        .formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .unwrap_or(swapchain_capabilities.formats[0]);
+
+    info!("using {:?} pipeline.", pipeline_kind);
+    let assets = load_assets(&scene_args);
+    let (mut scene, mut uniform) =
+        Scene::with_assets(&dev, pipeline_kind, size, swapchain_format, camera.projection(), &assets)
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+    let mut hot_reload = HotReload::new(&scene);
     //
     // depth texture
     //
     let (_, mut depth_view) = create_depth_texture(&dev, size);
-    //
-    // bind group
-    //
-    let (bind_group_layout, bind_group) = create_buffer_descriptors(&dev, &uniform_buffer);
-    //
-    // create pipelines
-    //
-    let swapchain_capabilities = surf.get_capabilities(&adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
-    let main_pipeline = create_main_pipeline(&dev, &bind_group_layout, swapchain_format);
 
     let mut surf_cfg = surf
         .get_default_config(&adapter, size.width, size.height)
         .unwrap();
+    surf_cfg.format = swapchain_format;
+    surf_cfg.present_mode = wgpu_core::choose_present_mode(config.present_mode, &swapchain_capabilities.present_modes);
+    // screenshots copy straight out of the swapchain texture
+    if swapchain_capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+        surf_cfg.usage |= wgpu::TextureUsages::COPY_SRC;
+    }
     surf.configure(&dev, &surf_cfg);
     //
     // imgui setup
     //
     let (mut imgui, mut platform, mut renderer) =
-        setup_imgui(&window, &dev, &queue, &surf_cfg, hidpi_factor);
+        setup_imgui(&window, &dev, &queue, &surf_cfg, hidpi_factor, config.imgui_ini.clone());
     //
     // audio setup
     //
-    let audio = Audio::new();
+    let mut audio = match audio_file {
+        Some(path) => {
+            let clip = Clip::load(&path).unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            info!("playing {}, {:.1} s.", path.display(), clip.duration());
+            let mut playback = Playback::new(clip);
+            playback.open_output();
+            playback.play();
+            AudioSource::File(playback)
+        }
+        None => AudioSource::Input(Audio::new(config.audio.clone())),
+    };
+    let mut audio_analysis = AudioAnalysis::default();
     //
     // main loop, window event handling
     //
     let mut frame_info = FrameInfo::default();
+    let start_time = Instant::now();
+    let mut screenshot = ScreenshotRequest::default();
+    let mut shadertoy_inputs = ShadertoyInputs::default();
 
-    let mut last_cursor = None;
+    let mut pacer = FramePacer::default();
 
-    let clear_color = wgpu::Color {
-        r: 0.1,
-        g: 0.2,
-        b: 0.3,
-        a: 1.0,
-    };
+    let mut last_cursor = None;
+    // a 0x0 surface can't be configured, nothing is drawn until the window is restored
+    let mut minimized = false;
 
     event_loop
         .run(|event, elwt| {
             let _ = (&instance, &adapter);
-            elwt.set_control_flow(ControlFlow::Poll);
-
             match event {
-                Event::AboutToWait => window.request_redraw(),
-                Event::WindowEvent { ref event, .. } => match event {
-                    WindowEvent::Resized(new_size) => {
-                        surf_cfg.width = new_size.width.max(1);
-                        surf_cfg.height = new_size.height.max(1);
-                        size = *new_size;
-                        surf.configure(&dev, &surf_cfg);
-                        depth_view = create_depth_texture(&dev, size).1;
-                        uniform.update_proj(Vec2::new(size.width as f32, size.height as f32));
-                        queue.write_buffer(&uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
-                        window.request_redraw();
+                Event::LoopExiting => wgpu_core::unwatch_device(&dev),
+                Event::AboutToWait if minimized => elwt.set_control_flow(ControlFlow::Wait),
+                Event::AboutToWait => {
+                    let now = Instant::now();
+                    // polled here, an idle window in low power mode still picks up edited shaders
+                    if hot_reload.as_mut().is_some_and(|hot_reload| hot_reload.update(&dev, &mut scene)) {
+                        pacer.input(now);
                     }
-                    WindowEvent::CloseRequested => elwt.exit(),
-                    WindowEvent::KeyboardInput { event, .. } => {
-                        handle_keyboard(
-                            event,
-                            elwt,
-                            &window,
-                            &queue,
-                            &mut uniform,
-                            &uniform_buffer,
-                        );
-                    }
-                    WindowEvent::RedrawRequested => {
-                        let delta_time = frame_info.fetch();
-
-                        imgui.io_mut().update_delta_time(delta_time);
-                        let frame = surf
-                            .get_current_texture()
-                            .expect("failed to acquire next swapchain texture.");
-
-                        uniform.time += 1;
-                        queue.write_buffer(&uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
-
-                        platform
-                            .prepare_frame(imgui.io_mut(), &window)
-                            .expect("failed to prepare frame.");
-                        let ui = imgui.frame();
-                        base_ui(&ui, frame_info.frame_time, frame_info.fps);
-
-                        let mut encoder: wgpu::CommandEncoder =
-                            dev.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                                label: None,
-                            });
-                        let view = frame
-                            .texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-
-                        if last_cursor != Some(ui.mouse_cursor()) {
-                            last_cursor = Some(ui.mouse_cursor());
-                            platform.prepare_render(ui, &window);
+
+                    let animating = camera.is_moving() || scene.is_animated() || audio.is_live();
+                    match pacer.pace(now, config.max_fps, config.low_power, animating) {
+                        Pace::Redraw => {
+                            elwt.set_control_flow(ControlFlow::Poll);
+                            window.request_redraw();
                         }
+                        Pace::WaitUntil(deadline) => elwt.set_control_flow(ControlFlow::WaitUntil(deadline)),
+                    }
+                }
+                Event::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { delta },
+                    ..
+                } => {
+                    pacer.input(Instant::now());
+                    camera.handle_mouse_motion(delta);
+                }
+                Event::WindowEvent { ref event, .. } => {
+                    if !matches!(event, WindowEvent::RedrawRequested) {
+                        pacer.input(Instant::now());
+                    }
 
+                    match event {
+                        WindowEvent::Resized(new_size) if new_size.width == 0 || new_size.height == 0 => minimized = true,
+                        WindowEvent::Resized(new_size) => {
+                            minimized = false;
+                            surf_cfg.width = new_size.width;
+                            surf_cfg.height = new_size.height;
+                            size = *new_size;
+                            surf.configure(&dev, &surf_cfg);
+                            depth_view = create_depth_texture(&dev, size).1;
+                            scene.resize(&dev, size);
+                            uniform.res = [size.width, size.height];
+                            queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+                            window.request_redraw();
+                        }
+                        WindowEvent::CloseRequested => elwt.exit(),
+                        WindowEvent::KeyboardInput { event, .. } => {
+                            handle_keyboard(
+                                event,
+                                elwt,
+                                &window,
+                                &queue,
+                                &mut uniform,
+                                &scene.uniform_buffer,
+                                &mut camera,
+                                &mut screenshot,
+                                imgui.io().want_capture_keyboard,
+                            );
+                        }
+                        // releases always go through, so looking around never gets stuck
+                        WindowEvent::MouseInput { state, button, .. }
+                            if !imgui.io().want_capture_mouse || *state == ElementState::Released =>
                         {
-                            let mut render_pass =
-                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            camera.handle_mouse_button(*button, *state);
+                            shadertoy_inputs.handle_mouse_button(*button, *state);
+                        }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            shadertoy_inputs.handle_cursor_moved(*position);
+                        }
+                        WindowEvent::MouseWheel { delta, .. } if !imgui.io().want_capture_mouse => {
+                            camera.handle_scroll(*delta);
+                        }
+                        WindowEvent::RedrawRequested if !minimized => {
+                            if device_lost.load(Ordering::Relaxed) {
+                                // everything created from the old device goes with it
+                                let recreated = block_on(wgpu_core::request_device(&adapter)).and_then(|(new_dev, new_queue)| {
+                                    let projection = camera.projection();
+                                    let (new_scene, _) =
+                                        Scene::with_assets(&new_dev, pipeline_kind, size, swapchain_format, projection, &assets)
+                                            .map_err(|e| AdapterError(e.to_string()))?;
+                                    Ok((new_dev, new_queue, new_scene))
+                                });
+                                match recreated {
+                                    Ok((new_dev, new_queue, new_scene)) => {
+                                        wgpu_core::unwatch_device(&dev);
+                                        (dev, queue, scene) = (new_dev, new_queue, new_scene);
+                                        device_lost = wgpu_core::watch_device(&dev);
+                                        surf.configure(&dev, &surf_cfg);
+                                        depth_view = create_depth_texture(&dev, size).1;
+                                        hot_reload = HotReload::new(&scene);
+                                        renderer = create_renderer(&mut imgui, &dev, &queue, surf_cfg.format);
+                                        info!("recreated the device.");
+                                    }
+                                    Err(e) => {
+                                        error!("failed to recreate the lost device: {}", e);
+                                        elwt.exit();
+                                        return;
+                                    }
+                                }
+                            }
+
+                            let frame = match surf.get_current_texture() {
+                                Ok(frame) => frame,
+                                Err(SurfaceError::Timeout) => {
+                                    warn!("timed out waiting for a swapchain texture, skipping the frame.");
+                                    return;
+                                }
+                                Err(e @ (SurfaceError::Outdated | SurfaceError::Lost)) => {
+                                    debug!("swapchain {:?}, reconfiguring.", e);
+                                    surf.configure(&dev, &surf_cfg);
+                                    return;
+                                }
+                                Err(SurfaceError::OutOfMemory) => {
+                                    error!("out of memory acquiring a swapchain texture.");
+                                    elwt.exit();
+                                    return;
+                                }
+                            };
+                            if pacer.woke() {
+                                frame_info.resume();
+                            }
+                            let delta_time = frame_info.fetch();
+
+                            uniform.time = start_time.elapsed().as_millis() as u32;
+                            audio.update(uniform.time);
+                            audio_analysis.update(&mut audio, delta_time.as_secs_f32());
+                            scene.write_audio(&queue, &audio_analysis.texels());
+
+                            imgui.io_mut().update_delta_time(delta_time);
+
+                            shadertoy_inputs.write_uniform(&mut uniform, delta_time.as_secs_f32(), SystemTime::now());
+                            audio_analysis.write_uniform(&mut uniform);
+                            camera.update(delta_time.as_secs_f32());
+                            camera.write_uniform(&mut uniform, surf_cfg.width as f32 / surf_cfg.height as f32);
+                            queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+                            platform
+                                .prepare_frame(imgui.io_mut(), &window)
+                                .expect("failed to prepare frame.");
+                            let ui = imgui.frame();
+                            base_ui(ui, &mut frame_info, &mut screenshot);
+                            if let Some(error) = hot_reload.as_ref().and_then(|h| h.error.as_ref()) {
+                                shader_error_ui(ui, error);
+                            }
+                            let log_level = config.log_level;
+                            let present_mode = config.present_mode;
+                            let mut save_config = settings_ui(ui, &mut config);
+                            match &mut audio {
+                                AudioSource::Input(input) => {
+                                    if audio_device_ui(ui, input) {
+                                        config.audio = input.selection().clone();
+                                        save_config = true;
+                                    }
+                                }
+                                AudioSource::File(playback) => playback_ui(ui, playback),
+                            }
+                            if config.log_level != log_level {
+                                log::set_max_level(config.log_level);
+                            }
+                            if save_config {
+                                if let Err(e) = config.save(&config_path) {
+                                    error!("saving the config: {}", e);
+                                }
+                            }
+
+                            let mut encoder: wgpu::CommandEncoder =
+                                dev.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                                     label: None,
-                                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                        view: &view,
-                                        resolve_target: None,
-                                        ops: wgpu::Operations {
-                                            load: wgpu::LoadOp::Clear(clear_color),
-                                            store: wgpu::StoreOp::Store,
-                                        },
-                                    })],
-                                    depth_stencil_attachment: Some(
-                                        wgpu::RenderPassDepthStencilAttachment {
-                                            view: &depth_view,
-                                            depth_ops: Some(wgpu::Operations {
-                                                load: wgpu::LoadOp::Clear(1.0),
-                                                store: wgpu::StoreOp::Store,
-                                            }),
-                                            stencil_ops: None,
-                                        },
-                                    ),
-                                    timestamp_writes: None,
-                                    occlusion_query_set: None,
                                 });
+                            let view = frame
+                                .texture
+                                .create_view(&wgpu::TextureViewDescriptor::default());
+
+                            if last_cursor != Some(ui.mouse_cursor()) {
+                                last_cursor = Some(ui.mouse_cursor());
+                                platform.prepare_render(ui, &window);
+                            }
 
-                            render_pass.set_pipeline(&main_pipeline);
-                            render_pass.set_bind_group(0, &bind_group, &[]);
-                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                            render_pass.set_index_buffer(
-                                index_buffer.slice(..),
-                                wgpu::IndexFormat::Uint32,
+                            let capture = match screenshot.pending {
+                                true => Capture::new(&dev, &frame.texture)
+                                    .map_err(|e| error!("{}", e))
+                                    .ok(),
+                                false => None,
+                            };
+                            screenshot.pending = false;
+
+                            scene.clear_color = config.clear_color;
+                            scene.render(&mut encoder, &view, &depth_view);
+
+                            if let Some(capture) = capture.as_ref().filter(|_| !screenshot.include_overlay) {
+                                capture.record(&mut encoder, &frame.texture);
+                            }
+
+                            imgui_render_pass(
+                                &dev,
+                                &queue,
+                                &mut encoder,
+                                &mut imgui,
+                                &mut renderer,
+                                &view,
                             );
-                            render_pass.draw_indexed(0..num_indices, 0, 0..1);
-                        }
 
-                        imgui_render_pass(
-                            &dev,
-                            &queue,
-                            &mut encoder,
-                            &mut imgui,
-                            &mut renderer,
-                            &view,
-                        );
-
-                        queue.submit(Some(encoder.finish()));
-                        frame.present();
+                            if let Some(capture) = capture.as_ref().filter(|_| screenshot.include_overlay) {
+                                capture.record(&mut encoder, &frame.texture);
+                            }
+
+                            queue.submit(Some(encoder.finish()));
+                            if let Some(capture) = capture {
+                                capture.save(&dev, Path::new("."));
+                            }
+                            frame.present();
+
+                            // the surface can't be reconfigured while a frame is out
+                            if config.present_mode != present_mode {
+                                surf_cfg.present_mode =
+                                    wgpu_core::choose_present_mode(config.present_mode, &swapchain_capabilities.present_modes);
+                                surf.configure(&dev, &surf_cfg);
+                            }
+                        }
+                        _ => {}
                     }
-                    _ => {}
-                },
+                }
                 _ => {}
             }
 
@@ -210,9 +363,119 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         .unwrap();
 }
 
+/// Renders a single frame without a window and writes it to `output`.
+async fn run_headless(
+    pipeline_kind: PipelineKind,
+    size: PhysicalSize<u32>,
+    adapter: &AdapterSelection,
+    assets: &SceneAssets,
+    output: &Path,
+) {
+    let (_instance, _adapter, dev, queue) = exit_on_error(wgpu_core::init_headless(adapter).await);
+
+    let camera = Scene::default_camera(pipeline_kind);
+    let (mut scene, mut uniform) =
+        Scene::with_assets(&dev, pipeline_kind, size, OFFSCREEN_FORMAT, camera.projection(), assets)
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+    let offscreen = Offscreen::new(&dev, size);
+
+    camera.write_uniform(&mut uniform, size.width as f32 / size.height as f32);
+    ShadertoyInputs::default().write_uniform(&mut uniform, 0.0, UNIX_EPOCH);
+    queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+    let pixels = offscreen.render_scene(&dev, &queue, &mut scene);
+    match save_png(output, size, &pixels) {
+        Ok(()) => info!("wrote {}.", output.display()),
+        Err(e) => {
+            error!("failed to write {}: {}", output.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn exit_on_error<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Reads the `--scene` octree, shaders are only read once the scene is built.
+fn load_assets(args: &SceneArgs) -> SceneAssets {
+    let octree = args.scene.as_ref().map(|path| {
+        if args.pipeline_kind != PipelineKind::Octree {
+            warn!("--scene only changes the octree pipeline.");
+        }
+        let bytes = exit_on_error(fs::read(path).map_err(|e| format!("{}: {}", path.display(), e)));
+        exit_on_error(Octree::from_file(&bytes).map_err(|e| format!("{}: {}", path.display(), e)))
+    });
+
+    SceneAssets {
+        shaders: args.shaders.clone().map_or(ShaderOrigin::Embedded, ShaderOrigin::Disk),
+        octree,
+    }
+}
+
+fn convert(input: &Path, output: &Path) -> Result<(), String> {
+    let bytes = fs::read(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    let octree = VoxModel::parse(&bytes)
+        .and_then(|model| model.to_octree())
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    let file = octree.to_file().map_err(|e| e.to_string())?;
+    fs::write(output, file).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    info!("wrote {}, an octree of depth {}.", output.display(), octree.depth());
+    Ok(())
+}
+
+fn print_devices(backends: Backends) {
+    println!("graphics adapters:");
+    let adapters = wgpu_core::list_adapters(backends);
+    if adapters.is_empty() {
+        println!("  none, `--backend all` lists every backend.");
+    }
+    for (i, (info, limits)) in adapters.iter().enumerate() {
+        println!("  {}", describe_adapter(i, info));
+        let driver = format!("{} {}", info.driver, info.driver_info);
+        if !driver.trim().is_empty() {
+            println!("     driver {}", driver.trim());
+        }
+        println!(
+            "     max texture {}, max buffer {} MiB, max storage binding {} MiB, max workgroup invocations {}",
+            limits.max_texture_dimension_2d,
+            limits.max_buffer_size >> 20,
+            limits.max_storage_buffer_binding_size >> 20,
+            limits.max_compute_invocations_per_workgroup
+        );
+    }
+
+    println!("audio inputs:");
+    for host in audio::list_devices() {
+        let default = if host.is_default { " (default)" } else { "" };
+        println!("  {}{}", host.name, default);
+        for device in host.devices {
+            let default = if device.is_default { " (default)" } else { "" };
+            let rates: Vec<String> = device
+                .configs
+                .iter()
+                .map(|c| format!("{}-{} Hz x{}", c.min_sample_rate().0, c.max_sample_rate().0, c.channels()))
+                .collect();
+            println!("    {}{}: {}", device.name, default, rates.join(", "));
+        }
+    }
+}
+
 fn main() {
-    env::set_var("RUST_LOG", "info");
-    let mut builder = Builder::from_default_env();
+    let cli = cli::parse(env::args().skip(1));
+    if let Ok(Cli { command: Command::Help(text), .. }) = &cli {
+        print!("{}", text);
+        return;
+    }
+
+    let mut builder = Builder::new();
 
     builder
         .target(Target::Stdout)
@@ -222,7 +485,8 @@ fn main() {
                 log::Level::Error => Style::new().bold().fg(Red),
                 log::Level::Warn => Style::new().bold().fg(Yellow),
                 log::Level::Info => Style::new().bold().fg(Blue),
-                _ => return Ok(()),
+                log::Level::Debug => Style::new().fg(Purple),
+                log::Level::Trace => Style::new().dimmed(),
             };
 
             buf.write_fmt(format_args!(
@@ -235,21 +499,67 @@ fn main() {
         .filter(None, LevelFilter::Trace)
         .init();
 
+    let cli = exit_on_error(cli);
+    let config = exit_on_error(Config::load(&cli.config));
+    let startup = config.with_overrides(&cli.overrides);
+    log::set_max_level(startup.log_level);
+    let size = PhysicalSize::new(startup.window_size.width, startup.window_size.height);
+
     println!();
     info!("logger initialized.");
 
-    let event_loop = EventLoop::new().unwrap();
+    match cli.command {
+        Command::Run { scene, audio } => {
+            let event_loop = EventLoop::new().unwrap();
 
-    let window = {
-        let size = LogicalSize::new(1280.0, 720.0);
-        WindowBuilder::new()
-            .with_inner_size(size)
-            .with_title(env!("CARGO_PKG_NAME"))
-            .build(&event_loop)
-            .unwrap()
-    };
+            let window = {
+                WindowBuilder::new()
+                    .with_inner_size(startup.window_size)
+                    .with_title(env!("CARGO_PKG_NAME"))
+                    .build(&event_loop)
+                    .unwrap()
+            };
+
+            // window.set_cursor_grab(CursorGrabMode::Locked).unwrap();
 
-    // window.set_cursor_grab(CursorGrabMode::Locked).unwrap();
+            block_on(run(event_loop, window, scene, audio, config, cli.config, cli.overrides));
+        }
+        Command::Render { scene, output, frames, fps, audio } => {
+            let assets = load_assets(&scene);
+            let adapter = startup.adapter_selection();
+            if frames == 1 && audio.is_none() && output.extension().is_some_and(|ext| ext == "png") {
+                block_on(run_headless(scene.pipeline_kind, size, &adapter, &assets, &output));
+                return;
+            }
 
-    block_on(run(event_loop, window));
+            let settings = ExportSettings {
+                pipeline_kind: scene.pipeline_kind,
+                size,
+                fps,
+                frames,
+                output,
+                assets,
+                adapter,
+                audio,
+            };
+            if let Err(e) = block_on(run_export(&settings)) {
+                error!("render failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Bench { scene, frames } => {
+            let settings = BenchSettings {
+                pipeline_kind: scene.pipeline_kind,
+                size,
+                frames,
+                assets: load_assets(&scene),
+                adapter: startup.adapter_selection(),
+            };
+            let report = exit_on_error(block_on(run_bench(&settings)));
+            info!("{}", report);
+        }
+        Command::Convert { input, output } => exit_on_error(convert(&input, &output)),
+        Command::Devices => print_devices(startup.backends),
+        Command::Help(_) => unreachable!("help is printed before logging starts."),
+    }
 }
@@ -0,0 +1,332 @@
+//! Runs the passes of a shadertoy project. Every buffer has two textures written on alternating
+//! frames, so a pass can read the last frame of any buffer, its own included.
+
+use crate::pipelines::{create_shader, create_shadertoy_pipeline, load_shader, PipelineError};
+use crate::shader_loader::{ShaderOrigin, FULLSCREEN_VERT};
+use crate::shadertoy_project::{Channel, Filter, Input, PassName, Project, Wrap, CHANNELS};
+use crate::spectrum::{AUDIO_TEXTURE_HEIGHT, AUDIO_TEXTURE_WIDTH};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, RenderPipeline, Texture,
+    TextureFormat, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+/// Filterable without extra features and precise enough for most simulations.
+pub const BUFFER_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+struct Target {
+    name: PassName,
+    _textures: [Texture; 2],
+    views: [TextureView; 2],
+}
+
+// what a pass sees as `iChannel0..3`, one bind group per frame parity
+struct PassBindings {
+    _resolution: Buffer,
+    bind_groups: [BindGroup; 2],
+}
+
+pub struct Multipass {
+    project: Project,
+    layout: BindGroupLayout,
+    _placeholder: Texture,
+    placeholder_view: TextureView,
+    // for channels with `input = "audio"`
+    audio_view: TextureView,
+    targets: Vec<Target>,
+    // one per buffer pass, the image pipeline belongs to the scene
+    pipelines: Vec<RenderPipeline>,
+    // one per pass, the image last
+    bindings: Vec<PassBindings>,
+    frame: u64,
+}
+
+fn create_texture(dev: &Device, label: &str, size: PhysicalSize<u32>) -> Texture {
+    dev.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: BUFFER_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+// bindings as declared in the shadertoy prelude
+fn create_channel_layout(dev: &Device) -> BindGroupLayout {
+    let textures = (0..CHANNELS as u32).map(|binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    });
+    let samplers = (0..CHANNELS as u32).map(|i| wgpu::BindGroupLayoutEntry {
+        binding: CHANNELS as u32 + i,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    });
+    let resolution = wgpu::BindGroupLayoutEntry {
+        binding: 2 * CHANNELS as u32,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    dev.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("shadertoy_channel_layout"),
+        entries: &textures.chain(samplers).chain([resolution]).collect::<Vec<_>>(),
+    })
+}
+
+fn create_sampler(dev: &Device, channel: Option<&Channel>) -> wgpu::Sampler {
+    let channel = channel.copied().unwrap_or(Channel {
+        input: Input::Audio,
+        filter: Filter::default(),
+        wrap: Wrap::default(),
+    });
+    let filter = match channel.filter {
+        Filter::Nearest => wgpu::FilterMode::Nearest,
+        Filter::Linear => wgpu::FilterMode::Linear,
+    };
+    let address_mode = match channel.wrap {
+        Wrap::Clamp => wgpu::AddressMode::ClampToEdge,
+        Wrap::Repeat => wgpu::AddressMode::Repeat,
+    };
+
+    dev.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("shadertoy_channel_sampler"),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        mag_filter: filter,
+        min_filter: filter,
+        ..Default::default()
+    })
+}
+
+/// Texture a pass reads for `input`. Buffers that already ran this frame give the one
+/// written this frame, the pass itself and later buffers the one from the last frame.
+fn read_index(pass: PassName, input: PassName, write: usize) -> usize {
+    if input < pass {
+        write
+    } else {
+        1 - write
+    }
+}
+
+impl Multipass {
+    /// Loads and compiles the project at `origin`, also returns the pipeline of the image pass
+    /// drawing to `format`. `audio_texture` is the scene's, see `Scene::write_audio`.
+    pub fn new(
+        dev: &Device,
+        scene_layout: &BindGroupLayout,
+        audio_texture: &Texture,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        origin: &ShaderOrigin,
+    ) -> Result<(Self, RenderPipeline), PipelineError> {
+        let project = Project::load(origin)?;
+        let layout = create_channel_layout(dev);
+        let vert_shader = load_shader(dev, &FULLSCREEN_VERT, origin)?;
+
+        let mut pipelines = Vec::new();
+        let mut image_pipeline = None;
+        for pass in &project.passes {
+            let frag_shader = create_shader(dev, pass.compile(origin)?, &pass.source_path())?;
+            let image = pass.name == PassName::Image;
+            let pipeline = create_shadertoy_pipeline(
+                dev,
+                &[scene_layout, &layout],
+                &vert_shader,
+                &frag_shader,
+                if image { format } else { BUFFER_FORMAT },
+                image,
+            )?;
+
+            match image {
+                true => image_pipeline = Some(pipeline),
+                false => pipelines.push(pipeline),
+            }
+        }
+
+        let placeholder = create_texture(dev, "shadertoy_placeholder", PhysicalSize::new(1, 1));
+        let mut multipass = Self {
+            project,
+            layout,
+            placeholder_view: placeholder.create_view(&wgpu::TextureViewDescriptor::default()),
+            _placeholder: placeholder,
+            audio_view: audio_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            targets: Vec::new(),
+            pipelines,
+            bindings: Vec::new(),
+            frame: 0,
+        };
+        multipass.resize(dev, size);
+
+        Ok((multipass, image_pipeline.expect("projects always end with the image.")))
+    }
+
+    /// Recreates the buffers at `size`, which clears them.
+    pub fn resize(&mut self, dev: &Device, size: PhysicalSize<u32>) {
+        self.targets = self
+            .project
+            .passes
+            .iter()
+            .filter(|pass| pass.name != PassName::Image)
+            .map(|pass| {
+                let textures = [0, 1].map(|_| create_texture(dev, pass.name.key(), size));
+                Target {
+                    name: pass.name,
+                    views: [0, 1].map(|i| textures[i].create_view(&wgpu::TextureViewDescriptor::default())),
+                    _textures: textures,
+                }
+            })
+            .collect();
+
+        self.bindings = self
+            .project
+            .passes
+            .iter()
+            .map(|pass| self.create_bindings(dev, pass.name, &pass.channels, size))
+            .collect();
+    }
+
+    fn create_bindings(
+        &self,
+        dev: &Device,
+        pass: PassName,
+        channels: &[Option<Channel>; CHANNELS],
+        size: PhysicalSize<u32>,
+    ) -> PassBindings {
+        let samplers = channels.each_ref().map(|channel| create_sampler(dev, channel.as_ref()));
+        // iChannelResolution, zero for unbound channels
+        let resolution = channels.map(|channel| match channel.map(|channel| channel.input) {
+            Some(Input::Buffer(_)) => [size.width.max(1) as f32, size.height.max(1) as f32, 1.0, 0.0],
+            Some(Input::Audio) => [AUDIO_TEXTURE_WIDTH as f32, AUDIO_TEXTURE_HEIGHT as f32, 1.0, 0.0],
+            None => [0.0; 4],
+        });
+        let resolution = dev.create_buffer_init(&BufferInitDescriptor {
+            label: Some("shadertoy_channel_resolution"),
+            contents: bytemuck::cast_slice(&resolution),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_groups = [0, 1].map(|write| {
+            let views = channels.map(|channel| match channel.map(|channel| channel.input) {
+                Some(Input::Buffer(input)) => {
+                    let target = self
+                        .targets
+                        .iter()
+                        .find(|target| target.name == input)
+                        .expect("the project checks that inputs exist.");
+                    &target.views[read_index(pass, input, write)]
+                }
+                Some(Input::Audio) => &self.audio_view,
+                None => &self.placeholder_view,
+            });
+
+            let textures = views.iter().enumerate().map(|(i, view)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+            let sampler_entries = samplers.iter().enumerate().map(|(i, sampler)| wgpu::BindGroupEntry {
+                binding: (CHANNELS + i) as u32,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            });
+            let resolution_entry = wgpu::BindGroupEntry {
+                binding: 2 * CHANNELS as u32,
+                resource: resolution.as_entire_binding(),
+            };
+
+            dev.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("shadertoy_channel_bind_group"),
+                layout: &self.layout,
+                entries: &textures.chain(sampler_entries).chain([resolution_entry]).collect::<Vec<_>>(),
+            })
+        });
+
+        PassBindings {
+            _resolution: resolution,
+            bind_groups,
+        }
+    }
+
+    fn write_index(&self) -> usize {
+        (self.frame % 2) as usize
+    }
+
+    /// The project file and every pass source, relative to the crate root.
+    pub fn files(&self) -> Vec<String> {
+        self.project.files()
+    }
+
+    /// Records the buffer passes, `scene_bind_group` holds the uniform.
+    pub fn render_buffers(&self, encoder: &mut CommandEncoder, scene_bind_group: &BindGroup) {
+        let write = self.write_index();
+
+        for ((target, pipeline), bindings) in self.targets.iter().zip(&self.pipelines).zip(&self.bindings) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(target.name.key()),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.views[write],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, scene_bind_group, &[]);
+            render_pass.set_bind_group(1, &bindings.bind_groups[write], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Channels of the image pass for the frame being recorded.
+    pub fn image_bind_group(&self) -> &BindGroup {
+        let image = self.bindings.last().expect("projects always end with the image.");
+        &image.bind_groups[self.write_index()]
+    }
+
+    /// Swaps the buffers once everything for a frame is recorded.
+    pub fn finish_frame(&mut self) {
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_read_latest_output() {
+        for write in [0, 1] {
+            // feedback from the last frame
+            assert_eq!(read_index(PassName::BufferA, PassName::BufferA, write), 1 - write);
+            assert_eq!(read_index(PassName::BufferA, PassName::BufferC, write), 1 - write);
+            // already written this frame
+            assert_eq!(read_index(PassName::BufferC, PassName::BufferA, write), write);
+            assert_eq!(read_index(PassName::Image, PassName::BufferD, write), write);
+        }
+    }
+}
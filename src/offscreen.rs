@@ -0,0 +1,173 @@
+use crate::pipelines::create_depth_texture;
+use crate::scene::Scene;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::mpsc;
+use wgpu::{Buffer, CommandEncoder, Device, Queue, Texture, TextureFormat, TextureView};
+use winit::dpi::PhysicalSize;
+
+pub const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Rows of a texture to buffer copy have to start at multiples of 256 bytes.
+pub fn padded_bytes_per_row(width: u32, bytes_per_pixel: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * bytes_per_pixel).div_ceil(align) * align
+}
+
+/// Strips the row padding of a texture copy, returns tightly packed rows.
+pub fn unpad_rows(data: &[u8], size: PhysicalSize<u32>, bytes_per_pixel: u32) -> Vec<u8> {
+    let row_bytes = (size.width * bytes_per_pixel) as usize;
+    let padded_row_bytes = padded_bytes_per_row(size.width, bytes_per_pixel) as usize;
+
+    data.chunks(padded_row_bytes)
+        .take(size.height as usize)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect()
+}
+
+pub fn create_readback_buffer(dev: &Device, size: PhysicalSize<u32>, bytes_per_pixel: u32) -> Buffer {
+    dev.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_buffer"),
+        size: (padded_bytes_per_row(size.width, bytes_per_pixel) * size.height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
+
+/// Records a copy of the whole `texture` into `buffer`, rows padded as the copy requires.
+pub fn copy_to_buffer(encoder: &mut CommandEncoder, texture: &Texture, buffer: &Buffer, bytes_per_pixel: u32) {
+    let size = texture.size();
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row(size.width, bytes_per_pixel)),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+}
+
+/// Waits for the submitted copy into `buffer` and returns its tightly packed rows.
+pub fn read_buffer(dev: &Device, buffer: &Buffer, size: PhysicalSize<u32>, bytes_per_pixel: u32) -> Vec<u8> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    dev.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("readback buffer was never mapped.")
+        .expect("failed to map readback buffer.");
+
+    let pixels = unpad_rows(&slice.get_mapped_range(), size, bytes_per_pixel);
+    buffer.unmap();
+
+    pixels
+}
+
+/// Writes tightly packed srgb rgba8 pixels as a png.
+pub fn save_png(path: &Path, size: PhysicalSize<u32>, pixels: &[u8]) -> Result<(), png::EncodingError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
+
+/// Color and depth target to render into without a surface, plus a buffer to read it back.
+pub struct Offscreen {
+    pub size: PhysicalSize<u32>,
+    pub texture: Texture,
+    pub view: TextureView,
+    pub depth_view: TextureView,
+
+    readback_buffer: Buffer,
+}
+
+impl Offscreen {
+    pub fn new(dev: &Device, size: PhysicalSize<u32>) -> Self {
+        let texture = dev.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (_, depth_view) = create_depth_texture(dev, size);
+
+        let readback_buffer = create_readback_buffer(dev, size, BYTES_PER_PIXEL);
+
+        Self {
+            size,
+            texture,
+            view,
+            depth_view,
+
+            readback_buffer,
+        }
+    }
+
+    /// Draws `scene` with whatever its uniform buffer holds and reads the frame back.
+    pub fn render_scene(&self, dev: &Device, queue: &Queue, scene: &mut Scene) -> Vec<u8> {
+        let mut encoder = dev.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("offscreen_encoder"),
+        });
+        scene.render(&mut encoder, &self.view, &self.depth_view);
+        self.copy_to_readback(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+
+        self.read_pixels(dev)
+    }
+
+    /// Records a copy of the color target into the readback buffer.
+    pub fn copy_to_readback(&self, encoder: &mut CommandEncoder) {
+        copy_to_buffer(encoder, &self.texture, &self.readback_buffer, BYTES_PER_PIXEL);
+    }
+
+    /// Waits for the submitted copy, returns rgba8 pixels in srgb, top row first.
+    pub fn read_pixels(&self, dev: &Device) -> Vec<u8> {
+        read_buffer(dev, &self.readback_buffer, self.size, BYTES_PER_PIXEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_aligned_and_unpadded() {
+        assert_eq!(padded_bytes_per_row(64, 4), 256);
+        assert_eq!(padded_bytes_per_row(65, 4), 512);
+        assert_eq!(padded_bytes_per_row(3, 4), 256);
+
+        let size = PhysicalSize::new(3, 2);
+        let mut data = vec![0u8; 512];
+        data[..12].copy_from_slice(&[1; 12]);
+        data[256..268].copy_from_slice(&[2; 12]);
+
+        let pixels = unpad_rows(&data, size, 4);
+        assert_eq!(pixels.len(), 24);
+        assert!(pixels[..12].iter().all(|&b| b == 1));
+        assert!(pixels[12..].iter().all(|&b| b == 2));
+    }
+}
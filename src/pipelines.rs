@@ -1,6 +1,53 @@
+use pollster::block_on;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
 use wgpu::{RenderPipeline, Texture, TextureView};
 use winit::dpi::PhysicalSize;
-use crate::vertex::Vertex;
+use crate::shader_loader::{
+    ShaderError, ShaderFile, ShaderOrigin, FULLSCREEN_VERT, MESH_FRAG, MESH_VERT, OCTREE_WGSL,
+};
+use crate::shadertoy_project::ProjectError;
+use crate::svo::SvoError;
+use crate::uniform::{Uniform, UniformLayoutError};
+use crate::vertex::{Instance, Vertex};
+
+/// Which pipeline draws the scene, picked at startup.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PipelineKind {
+    /// glsl shaders rendering the cube mesh
+    #[default]
+    Mesh,
+    /// wgsl raymarcher traversing the svo buffer on a full screen quad
+    Octree,
+    /// unmodified shadertoy `mainImage` on a full screen triangle
+    Shadertoy,
+}
+
+impl FromStr for PipelineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mesh" => Ok(PipelineKind::Mesh),
+            "octree" => Ok(PipelineKind::Octree),
+            "shadertoy" => Ok(PipelineKind::Shadertoy),
+            _ => Err(format!("unknown pipeline '{s}', expected 'mesh', 'octree' or 'shadertoy'.")),
+        }
+    }
+}
+
+impl PipelineKind {
+    /// Shaders the pipeline is built from, it has to be rebuilt when one of them changes.
+    /// Shadertoy adds the files of its project.
+    pub fn shader_files(&self) -> &'static [ShaderFile] {
+        match self {
+            PipelineKind::Mesh => &[MESH_VERT, MESH_FRAG],
+            PipelineKind::Octree => &[OCTREE_WGSL],
+            PipelineKind::Shadertoy => &[FULLSCREEN_VERT],
+        }
+    }
+}
 
 pub fn create_depth_texture(dev: &wgpu::Device, size: PhysicalSize<u32>) -> (Texture, TextureView) {
     let depth_texture = dev.create_texture(&wgpu::TextureDescriptor {
@@ -23,9 +70,89 @@ pub fn create_depth_texture(dev: &wgpu::Device, size: PhysicalSize<u32>) -> (Tex
     (depth_texture, depth_view)
 }
 
-pub fn create_main_pipeline(dev: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, swapchain_format: wgpu::TextureFormat) -> RenderPipeline {
-    let vert_shader = unsafe { dev.create_shader_module_spirv(&wgpu::include_spirv_raw!("shader/glsl/vert.spv")) };
-    let frag_shader = unsafe { dev.create_shader_module_spirv(&wgpu::include_spirv_raw!("shader/glsl/frag.spv")) };
+/// Why a pipeline couldn't be built, the shader, its uniform block, the shadertoy project or
+/// the octree is off or wgpu rejected the result, e.g. because the stages don't fit together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipelineError {
+    Shader(ShaderError),
+    Layout(UniformLayoutError),
+    Project(ProjectError),
+    Scene(SvoError),
+    Device(String),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::Shader(e) => e.fmt(f),
+            PipelineError::Layout(e) => e.fmt(f),
+            PipelineError::Project(e) => e.fmt(f),
+            PipelineError::Scene(e) => e.fmt(f),
+            PipelineError::Device(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<ShaderError> for PipelineError {
+    fn from(e: ShaderError) -> Self {
+        PipelineError::Shader(e)
+    }
+}
+
+impl From<UniformLayoutError> for PipelineError {
+    fn from(e: UniformLayoutError) -> Self {
+        PipelineError::Layout(e)
+    }
+}
+
+impl From<SvoError> for PipelineError {
+    fn from(e: SvoError) -> Self {
+        PipelineError::Scene(e)
+    }
+}
+
+impl From<ProjectError> for PipelineError {
+    fn from(e: ProjectError) -> Self {
+        PipelineError::Project(e)
+    }
+}
+
+// validation errors are returned instead of going to the device's panicking error handler
+fn checked<T>(dev: &wgpu::Device, create: impl FnOnce() -> T) -> Result<T, PipelineError> {
+    dev.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+
+    match block_on(dev.pop_error_scope()) {
+        Some(e) => Err(PipelineError::Device(e.to_string())),
+        None => Ok(created),
+    }
+}
+
+/// Checks the uniform block of a compiled shader and hands it to wgpu.
+pub fn create_shader(dev: &wgpu::Device, module: naga::Module, path: &str) -> Result<wgpu::ShaderModule, PipelineError> {
+    if Uniform::is_used_by(&module) {
+        Uniform::validate_layout(&module, path)?;
+    }
+
+    checked(dev, || {
+        dev.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(path),
+            source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+        })
+    })
+}
+
+/// Compiles `file` from `origin`, then see `create_shader`.
+pub fn load_shader(dev: &wgpu::Device, file: &ShaderFile, origin: &ShaderOrigin) -> Result<wgpu::ShaderModule, PipelineError> {
+    create_shader(dev, file.load(origin)?, file.path)
+}
+
+/// `depth_compare` has to match the projection, see `Projection::depth_compare`.
+pub fn create_main_pipeline(dev: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, swapchain_format: wgpu::TextureFormat, depth_compare: wgpu::CompareFunction, origin: &ShaderOrigin) -> Result<RenderPipeline, PipelineError> {
+    let vert_shader = load_shader(dev, &MESH_VERT, origin)?;
+    let frag_shader = load_shader(dev, &MESH_FRAG, origin)?;
 
     let pipeline_layout = dev.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("render_pipeline_layout"),
@@ -33,13 +160,13 @@ pub fn create_main_pipeline(dev: &wgpu::Device, bind_group_layout: &wgpu::BindGr
         push_constant_ranges: &[],
     });
 
-    dev.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    checked(dev, || dev.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
             module: &vert_shader,
             entry_point: "main",
-            buffers: &[Vertex::desc()],
+            buffers: &[Vertex::desc(), Instance::desc()],
         },
         fragment: Some(wgpu::FragmentState {
             module: &frag_shader,
@@ -47,19 +174,96 @@ pub fn create_main_pipeline(dev: &wgpu::Device, bind_group_layout: &wgpu::BindGr
             targets: &[Some(swapchain_format.into())],
         }),
         primitive: wgpu::PrimitiveState {
+            // cube faces wind clockwise seen from outside
             cull_mode: Some(wgpu::Face::Back),
-            front_face: wgpu::FrontFace::Ccw,
+            front_face: wgpu::FrontFace::Cw,
 
             ..Default::default()
         },
         depth_stencil: Some(wgpu::DepthStencilState {
             format: wgpu::TextureFormat::Depth32Float,
             depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_compare,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState::default(),
         multiview: None,
-    })
-}
\ No newline at end of file
+    }))
+}
+
+pub fn create_octree_pipeline(dev: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, swapchain_format: wgpu::TextureFormat, origin: &ShaderOrigin) -> Result<RenderPipeline, PipelineError> {
+    let shader = load_shader(dev, &OCTREE_WGSL, origin)?;
+
+    let pipeline_layout = dev.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("octree_pipeline_layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    checked(dev, || dev.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("octree_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(swapchain_format.into())],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: None,
+            front_face: wgpu::FrontFace::Ccw,
+
+            ..Default::default()
+        },
+        // the quad covers the whole screen, depth is only kept to share the render pass
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    }))
+}
+
+/// Runs a shadertoy pass for every pixel of `format`, the image pass draws
+/// in the scene's render pass and needs its depth state, buffers don't have one.
+pub fn create_shadertoy_pipeline(dev: &wgpu::Device, bind_group_layouts: &[&wgpu::BindGroupLayout], vert_shader: &wgpu::ShaderModule, frag_shader: &wgpu::ShaderModule, format: wgpu::TextureFormat, with_depth: bool) -> Result<RenderPipeline, PipelineError> {
+    let pipeline_layout = dev.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("shadertoy_pipeline_layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    checked(dev, || dev.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("shadertoy_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: vert_shader,
+            entry_point: "main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: frag_shader,
+            entry_point: "main",
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: with_depth.then(|| wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    }))
+}
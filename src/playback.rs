@@ -0,0 +1,394 @@
+//! Plays a decoded clip in step with `uniform.time`, for the analyzers and, when there's an
+//! output device, for the speakers. The same times always give the same samples, so exports
+//! of audio reactive shaders are reproducible.
+
+use crate::audio::AudioError;
+use crate::audio_file::Clip;
+use crate::ring_buffer::{ring_buffer, Consumer, Producer};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use log::{info, warn};
+
+/// A third of a second of interleaved stereo at 48 kHz, refilled every frame.
+const OUTPUT_CAPACITY: usize = 1 << 15;
+/// Covers a slow frame or two without the device running dry.
+const OUTPUT_LEAD_MS: u64 = 50;
+
+// what the audio thread needs to play interleaved clip frames on a device
+struct Resampler {
+    samples: Consumer,
+    clip_channels: usize,
+    // clip frames per device frame
+    step: f64,
+    // position between `previous` and `next`
+    fraction: f64,
+    previous: Vec<f32>,
+    next: Vec<f32>,
+}
+
+impl Resampler {
+    fn fill<T: SizedSample + FromSample<f32>>(&mut self, out: &mut [T], device_channels: usize) {
+        for frame in out.chunks_exact_mut(device_channels) {
+            while self.fraction >= 1.0 {
+                self.fraction -= 1.0;
+                std::mem::swap(&mut self.previous, &mut self.next);
+                // running dry plays silence, e.g. while paused. a frame the producer is still
+                // pushing stays queued, popping part of it would swap the channels from then on
+                if self.samples.len() >= self.clip_channels {
+                    self.samples.pop_slice(&mut self.next);
+                } else {
+                    self.next.fill(0.0);
+                }
+            }
+
+            let t = self.fraction as f32;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let c = channel % self.clip_channels;
+                *sample = T::from_sample(self.previous[c] * (1.0 - t) + self.next[c] * t);
+            }
+            self.fraction += self.step;
+        }
+    }
+}
+
+struct Output {
+    _stream: Stream,
+    samples: Producer,
+}
+
+fn build_output<T>(device: &cpal::Device, config: &StreamConfig, mut resampler: Resampler) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |out: &mut [T], _: &cpal::OutputCallbackInfo| resampler.fill(out, channels),
+        |e| warn!("audio output: {}", e),
+        None,
+    )
+}
+
+fn open_output(clip: &Clip) -> Result<Output, AudioError> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| AudioError(format!("{} has no default output device.", host.id().name())))?;
+    let name = device.name().unwrap_or_default();
+    let supported = device.default_output_config().map_err(|e| AudioError(format!("{name}: {e}")))?;
+    let config = supported.config();
+
+    let (producer, consumer) = ring_buffer(OUTPUT_CAPACITY);
+    let channels = clip.channels as usize;
+    let resampler = Resampler {
+        samples: consumer,
+        clip_channels: channels,
+        step: clip.sample_rate as f64 / config.sample_rate.0 as f64,
+        fraction: 0.0,
+        previous: vec![0.0; channels],
+        next: vec![0.0; channels],
+    };
+
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_output::<f32>(&device, &config, resampler),
+        SampleFormat::I16 => build_output::<i16>(&device, &config, resampler),
+        SampleFormat::U16 => build_output::<u16>(&device, &config, resampler),
+        format => return Err(AudioError(format!("{name}: unsupported sample format {format}."))),
+    }
+    .map_err(|e| AudioError(format!("{name}: {e}")))?;
+    stream.play().map_err(|e| AudioError(format!("{name}: {e}")))?;
+    info!("playing audio on {} at {} Hz.", name, config.sample_rate.0);
+
+    Ok(Output { _stream: stream, samples: producer })
+}
+
+/// Transport for one clip. Positions are in frames and keep counting up across loops,
+/// `% clip.len()` gives the frame in the clip.
+pub struct Playback {
+    clip: Clip,
+    playing: bool,
+    looping: bool,
+    // `uniform.time` and position the playhead moves on from
+    anchor: Option<(u32, u64)>,
+    last_time: u32,
+    playhead: u64,
+    // next frame for the analyzers and the speakers
+    read_cursor: u64,
+    output_cursor: u64,
+    output: Option<Output>,
+}
+
+impl Playback {
+    /// Starts paused at the beginning and silent, see `open_output`.
+    pub fn new(clip: Clip) -> Self {
+        Self {
+            clip,
+            playing: false,
+            looping: true,
+            anchor: None,
+            last_time: 0,
+            playhead: 0,
+            read_cursor: 0,
+            output_cursor: 0,
+            output: None,
+        }
+    }
+
+    /// Also plays the clip on the default output device, stays silent if there's none.
+    pub fn open_output(&mut self) {
+        match open_output(&self.clip) {
+            Ok(output) => self.output = Some(output),
+            Err(e) => warn!("playing without sound: {}", e),
+        }
+    }
+
+    pub fn clip(&self) -> &Clip {
+        &self.clip
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn play(&mut self) {
+        if self.playing || self.clip.is_empty() {
+            return;
+        }
+        // starting again after the end
+        if !self.looping && self.playhead >= self.clip.len() as u64 {
+            self.jump(0);
+        }
+        self.playing = true;
+        self.anchor = Some((self.last_time, self.playhead));
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.anchor = None;
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        let frame = self.position_frames();
+        self.looping = looping;
+        self.jump(frame);
+    }
+
+    /// Moves the playhead to `seconds`, clamped to the clip.
+    pub fn seek(&mut self, seconds: f64) {
+        let frame = (seconds.max(0.0) * self.clip.sample_rate as f64) as u64;
+        self.jump(frame.min(self.clip.len() as u64));
+    }
+
+    // skips everything before `frame` instead of playing it
+    fn jump(&mut self, frame: u64) {
+        self.playhead = frame;
+        self.read_cursor = frame;
+        self.output_cursor = frame;
+        self.anchor = self.playing.then_some((self.last_time, frame));
+    }
+
+    fn position_frames(&self) -> u64 {
+        let len = self.clip.len() as u64;
+        match self.playhead {
+            playhead if playhead >= len && !self.looping => len,
+            playhead => playhead % len.max(1),
+        }
+    }
+
+    /// Seconds into the clip.
+    pub fn position(&self) -> f64 {
+        self.position_frames() as f64 / self.clip.sample_rate as f64
+    }
+
+    /// Moves the playhead along with `time`, the current `uniform.time` in milliseconds.
+    pub fn sync(&mut self, time: u32) {
+        self.last_time = time;
+        let Some((anchor_time, anchor_frame)) = self.anchor else {
+            return;
+        };
+
+        let elapsed = time.saturating_sub(anchor_time) as u64;
+        self.playhead = anchor_frame + elapsed * self.clip.sample_rate as u64 / 1000;
+
+        let len = self.clip.len() as u64;
+        if !self.looping && self.playhead >= len {
+            self.playhead = len;
+            self.pause();
+        }
+
+        self.feed_output();
+    }
+
+    fn feed_output(&mut self) {
+        let Some(output) = self.output.as_mut() else {
+            return;
+        };
+
+        let len = self.clip.len() as u64;
+        let channels = self.clip.channels as u64;
+        // what the device buffers ahead, so the frame being heard is the playhead
+        let mut target = self.playhead + self.clip.sample_rate as u64 * OUTPUT_LEAD_MS / 1000;
+        if !self.looping {
+            target = target.min(len);
+        }
+        // fell behind, e.g. after a long frame, skip ahead rather than lag
+        let capacity = (OUTPUT_CAPACITY as u64 / channels).saturating_sub(1);
+        self.output_cursor = self.output_cursor.max(target.saturating_sub(capacity));
+
+        while self.output_cursor < target {
+            let start = (self.output_cursor % len) as usize;
+            let end = start + ((target - self.output_cursor) as usize).min(self.clip.len() - start);
+            let frames = &self.clip.samples[start * channels as usize..end * channels as usize];
+            output.samples.push_slice(frames);
+            self.output_cursor += (end - start) as u64;
+        }
+    }
+
+    /// Moves the mono samples played since the last call into `out`, the newest ones
+    /// if there are more than fit, returns how many there were.
+    pub fn read(&mut self, out: &mut [f32]) -> usize {
+        let len = self.clip.len() as u64;
+        self.read_cursor = self.read_cursor.max(self.playhead.saturating_sub(out.len() as u64));
+
+        let count = (self.playhead - self.read_cursor) as usize;
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            *sample = self.clip.mono[((self.read_cursor + i as u64) % len) as usize];
+        }
+        self.read_cursor = self.playhead;
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a clip of 1000 frames at 1 kHz, each sample is its frame number
+    fn counting() -> Playback {
+        let samples: Vec<f32> = (0..1000).map(|i| i as f32).collect();
+        Playback::new(Clip::new(1000, 1, samples))
+    }
+
+    fn read_all(playback: &mut Playback) -> Vec<f32> {
+        let mut out = vec![0.0; 4096];
+        let count = playback.read(&mut out);
+        out.truncate(count);
+        out
+    }
+
+    #[test]
+    fn follows_time() {
+        let mut playback = counting();
+        playback.sync(5_000);
+        playback.play();
+        playback.sync(5_010);
+        assert_eq!(read_all(&mut playback), (0..10).map(|i| i as f32).collect::<Vec<_>>());
+        playback.sync(5_013);
+        assert_eq!(read_all(&mut playback), [10.0, 11.0, 12.0]);
+        assert_eq!(playback.position(), 0.013);
+    }
+
+    #[test]
+    fn pauses_and_seeks() {
+        let mut playback = counting();
+        playback.play();
+        playback.sync(100);
+        playback.pause();
+        playback.sync(500);
+        assert_eq!(playback.position(), 0.1);
+        assert_eq!(read_all(&mut playback).len(), 100);
+
+        playback.seek(0.9);
+        playback.play();
+        playback.sync(502);
+        assert_eq!(read_all(&mut playback), [900.0, 901.0]);
+
+        // seeking past the end stops at the end
+        playback.seek(5.0);
+        assert_eq!(playback.position(), 0.0);
+        playback.set_looping(false);
+        playback.seek(5.0);
+        assert_eq!(playback.position(), 1.0);
+    }
+
+    #[test]
+    fn loops_or_stops_at_end() {
+        let mut playback = counting();
+        playback.seek(0.998);
+        playback.play();
+        playback.sync(4);
+        assert_eq!(read_all(&mut playback), [998.0, 999.0, 0.0, 1.0]);
+        assert_eq!(playback.position(), 0.002);
+
+        playback.set_looping(false);
+        playback.seek(0.998);
+        playback.sync(10);
+        assert_eq!(read_all(&mut playback), [998.0, 999.0]);
+        assert!(!playback.is_playing());
+        assert_eq!(playback.position(), 1.0);
+
+        // play after the end starts over
+        playback.play();
+        playback.sync(11);
+        assert_eq!(read_all(&mut playback), [0.0]);
+    }
+
+    #[test]
+    fn keeps_newest_when_reading_late() {
+        let mut playback = counting();
+        playback.play();
+        playback.sync(600);
+        let mut out = [0.0; 4];
+        assert_eq!(playback.read(&mut out), 4);
+        assert_eq!(out, [596.0, 597.0, 598.0, 599.0]);
+    }
+
+    #[test]
+    fn resamples_for_the_device() {
+        let (mut producer, consumer) = ring_buffer(64);
+        producer.push_slice(&[0.0, 10.0, 1.0, 11.0, 2.0, 12.0]);
+        let mut resampler = Resampler {
+            samples: consumer,
+            clip_channels: 2,
+            step: 0.5,
+            // reads the first two frames right away
+            fraction: 2.0,
+            previous: vec![0.0; 2],
+            next: vec![0.0; 2],
+        };
+
+        // twice the rate and one more channel than the clip
+        let mut out = [0.0f32; 12];
+        resampler.fill(&mut out, 3);
+        assert_eq!(out, [0.0, 10.0, 0.0, 0.5, 10.5, 0.5, 1.0, 11.0, 1.0, 1.5, 11.5, 1.5]);
+    }
+
+    #[test]
+    fn waits_for_whole_frames() {
+        let (mut producer, consumer) = ring_buffer(64);
+        let mut resampler = Resampler {
+            samples: consumer,
+            clip_channels: 2,
+            step: 1.0,
+            fraction: 1.0,
+            previous: vec![0.0; 2],
+            next: vec![0.0; 2],
+        };
+
+        // half a frame plays silence and stays queued, the output runs a frame behind
+        producer.push_slice(&[0.0, 10.0, 1.0]);
+        let mut out = [0.0f32; 6];
+        resampler.fill(&mut out, 2);
+        assert_eq!(out, [0.0, 0.0, 0.0, 10.0, 0.0, 0.0]);
+
+        producer.push_slice(&[11.0]);
+        let mut out = [0.0f32; 4];
+        resampler.fill(&mut out, 2);
+        assert_eq!(out, [0.0, 0.0, 1.0, 11.0]);
+    }
+}
@@ -0,0 +1,145 @@
+use glam::Mat4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic,
+}
+
+/// Projection shared by both camera kinds.
+///
+/// With `reversed_z` near maps to depth 1 and far to 0, which spreads the precision of the
+/// `Depth32Float` depth texture evenly. The depth test and clear value have to follow,
+/// see `depth_compare` and `depth_clear`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    pub kind: ProjectionKind,
+    // vertical, degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub reversed_z: bool,
+}
+
+impl Projection {
+    pub fn perspective(fov: f32, near: f32, far: f32) -> Self {
+        Self {
+            kind: ProjectionKind::Perspective,
+            fov,
+            near,
+            far,
+            reversed_z: true,
+        }
+    }
+
+    pub fn toggle_kind(&mut self) {
+        self.kind = match self.kind {
+            ProjectionKind::Perspective => ProjectionKind::Orthographic,
+            ProjectionKind::Orthographic => ProjectionKind::Perspective,
+        };
+    }
+
+    /// Tangent of half the vertical fov, the height of the ray basis planes.
+    pub fn half_height(&self) -> f32 {
+        (self.fov.to_radians() * 0.5).tan()
+    }
+
+    /// Projection matrix for a left handed view space.
+    ///
+    /// The orthographic volume is sized to frame things at `focus_distance` the same way
+    /// the perspective one does, so switching between them keeps the target in view.
+    pub fn matrix(&self, aspect_ratio: f32, focus_distance: f32) -> Mat4 {
+        let (near, far) = match self.reversed_z {
+            true => (self.far, self.near),
+            false => (self.near, self.far),
+        };
+
+        match self.kind {
+            ProjectionKind::Perspective => {
+                Mat4::perspective_lh(self.fov.to_radians(), aspect_ratio, near, far)
+            }
+            ProjectionKind::Orthographic => {
+                let half_height = self.half_height() * focus_distance;
+                let half_width = half_height * aspect_ratio;
+                Mat4::orthographic_lh(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        }
+    }
+
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        match self.reversed_z {
+            true => wgpu::CompareFunction::Greater,
+            false => wgpu::CompareFunction::Less,
+        }
+    }
+
+    pub fn depth_clear(&self) -> f32 {
+        match self.reversed_z {
+            true => 0.0,
+            false => 1.0,
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::perspective(60.0, 0.01, 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn depth(proj: &Projection, z: f32) -> f32 {
+        proj.matrix(1.0, 1.0).project_point3(Vec3::new(0.0, 0.0, z)).z
+    }
+
+    #[test]
+    fn depth_range() {
+        for kind in [ProjectionKind::Perspective, ProjectionKind::Orthographic] {
+            let mut proj = Projection {
+                kind,
+                ..Projection::perspective(60.0, 0.5, 50.0)
+            };
+
+            proj.reversed_z = false;
+            assert!(depth(&proj, 0.5).abs() < 1e-5);
+            assert!((depth(&proj, 50.0) - 1.0).abs() < 1e-5);
+            assert!(depth(&proj, 1.0) < depth(&proj, 2.0));
+
+            proj.reversed_z = true;
+            assert!((depth(&proj, 0.5) - 1.0).abs() < 1e-5);
+            assert!(depth(&proj, 50.0).abs() < 1e-5);
+            assert!(depth(&proj, 1.0) > depth(&proj, 2.0));
+        }
+    }
+
+    #[test]
+    fn depth_test_follows_reversed_z() {
+        let mut proj = Projection::default();
+        assert_eq!(proj.depth_compare(), wgpu::CompareFunction::Greater);
+        assert_eq!(proj.depth_clear(), 0.0);
+
+        proj.reversed_z = false;
+        assert_eq!(proj.depth_compare(), wgpu::CompareFunction::Less);
+        assert_eq!(proj.depth_clear(), 1.0);
+    }
+
+    #[test]
+    fn orthographic_frames_focus_distance() {
+        let mut proj = Projection::perspective(90.0, 0.1, 100.0);
+        let aspect_ratio = 2.0;
+        let focus = 3.0;
+        // top right corner of the perspective frustum at the focus distance
+        let corner = Vec3::new(focus * aspect_ratio, focus, focus);
+
+        let perspective = proj.matrix(aspect_ratio, focus).project_point3(corner);
+        proj.toggle_kind();
+        let orthographic = proj.matrix(aspect_ratio, focus).project_point3(corner);
+
+        assert!(perspective.truncate().abs_diff_eq(glam::Vec2::ONE, 1e-5));
+        assert!(orthographic.truncate().abs_diff_eq(glam::Vec2::ONE, 1e-5));
+    }
+}
@@ -0,0 +1,156 @@
+//! Single producer, single consumer queue of samples that never locks or allocates once created,
+//! so the audio callback can push into it.
+
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Shared {
+    // f32 bits, atomics keep this free of unsafe code
+    slots: Box<[AtomicU32]>,
+    // total samples written and read, the difference is the fill level
+    written: AtomicUsize,
+    read: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+/// Write end, usually owned by the audio thread.
+pub struct Producer {
+    shared: Arc<Shared>,
+}
+
+/// Read end, usually owned by the render thread.
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    assert!(capacity > 0, "a ring buffer needs room for one sample.");
+
+    let shared = Arc::new(Shared {
+        slots: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        written: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+
+    (Producer { shared: shared.clone() }, Consumer { shared })
+}
+
+impl Producer {
+    /// Returns false and counts the sample as dropped while the buffer is full.
+    pub fn push(&mut self, sample: f32) -> bool {
+        let shared = &*self.shared;
+        let written = shared.written.load(Ordering::Relaxed);
+        let read = shared.read.load(Ordering::Acquire);
+        if written.wrapping_sub(read) == shared.slots.len() {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        shared.slots[written % shared.slots.len()].store(sample.to_bits(), Ordering::Relaxed);
+        shared.written.store(written.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Number of samples that fit, the rest is dropped.
+    pub fn push_slice(&mut self, samples: &[f32]) -> usize {
+        samples.iter().filter(|&&sample| self.push(sample)).count()
+    }
+}
+
+impl Consumer {
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let written = self.shared.written.load(Ordering::Acquire);
+        written.wrapping_sub(self.shared.read.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Samples the producer couldn't push since the buffer was created.
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Moves the oldest samples into `out`, returns how many there were.
+    pub fn pop_slice(&mut self, out: &mut [f32]) -> usize {
+        let shared = &*self.shared;
+        let read = shared.read.load(Ordering::Relaxed);
+        let count = self.len().min(out.len());
+
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            let slot = &shared.slots[read.wrapping_add(i) % shared.slots.len()];
+            *sample = f32::from_bits(slot.load(Ordering::Relaxed));
+        }
+        shared.read.store(read.wrapping_add(count), Ordering::Release);
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn keeps_order_across_wrap() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        let mut out = [0.0; 4];
+
+        assert_eq!(producer.push_slice(&[1.0, 2.0, 3.0]), 3);
+        assert_eq!(consumer.pop_slice(&mut out[..2]), 2);
+        assert_eq!(out[..2], [1.0, 2.0]);
+
+        assert_eq!(producer.push_slice(&[4.0, 5.0, 6.0]), 3);
+        assert_eq!(consumer.len(), 4);
+        assert_eq!(consumer.pop_slice(&mut out), 4);
+        assert_eq!(out, [3.0, 4.0, 5.0, 6.0]);
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.pop_slice(&mut out), 0);
+    }
+
+    #[test]
+    fn drops_samples_when_full() {
+        let (mut producer, mut consumer) = ring_buffer(2);
+
+        assert_eq!(producer.push_slice(&[1.0, 2.0, 3.0, 4.0]), 2);
+        assert!(!producer.push(5.0));
+        assert_eq!(consumer.dropped(), 3);
+
+        let mut out = [0.0; 3];
+        assert_eq!(consumer.pop_slice(&mut out), 2);
+        assert_eq!(out[..2], [1.0, 2.0]);
+        assert!(producer.push(6.0));
+    }
+
+    #[test]
+    fn passes_samples_between_threads() {
+        const COUNT: usize = 100_000;
+        let (mut producer, mut consumer) = ring_buffer(64);
+
+        let writer = thread::spawn(move || {
+            for i in 0..COUNT {
+                while !producer.push(i as f32) {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        let mut out = [0.0; 16];
+        while expected < COUNT {
+            let count = consumer.pop_slice(&mut out);
+            for sample in &out[..count] {
+                assert_eq!(*sample, expected as f32);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
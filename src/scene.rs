@@ -0,0 +1,311 @@
+use crate::camera::{Camera, CameraMode};
+use crate::multipass::Multipass;
+use crate::pipelines::{create_main_pipeline, create_octree_pipeline, PipelineError, PipelineKind};
+use crate::projection::Projection;
+use crate::shader_loader::ShaderOrigin;
+use crate::streaming::{
+    create_audio_texture, create_buffer_descriptors, create_instance_buffer, create_polygon_buffers,
+    create_svo_buffer, create_uniform_buffer, write_audio_texture,
+};
+use crate::svo::{self, Octree};
+use crate::uniform::Uniform;
+use crate::vertex::{
+    Instance, Vertex, CUBE_INDICES, CUBE_UV_COORDS, CUBE_VERTEX_POSITIONS, QUAD_INDICES,
+    QUAD_UV_COORDS, QUAD_VERTEX_POSITIONS,
+};
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, CompareFunction, Device, Queue,
+    RenderPipeline, Texture, TextureFormat, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+const SVO_DEPTH: u32 = 6;
+
+pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// What a scene is built from besides its pipeline kind, the defaults are what's built into the binary.
+#[derive(Clone, Debug, Default)]
+pub struct SceneAssets {
+    pub shaders: ShaderOrigin,
+    /// drawn by the octree pipeline, `None` is `svo::demo_scene`
+    pub octree: Option<Octree>,
+}
+
+/// Everything needed to draw one pipeline kind, shared by the window and headless rendering.
+pub struct Scene {
+    pub kind: PipelineKind,
+    pub uniform_buffer: Buffer,
+    /// behind everything the pipeline doesn't cover, `CLEAR_COLOR` at first
+    pub clear_color: wgpu::Color,
+
+    // shadertoy's audio texture, bound next to the uniform
+    audio_texture: Texture,
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    // shadertoy buffers, `pipeline` draws its image pass
+    multipass: Option<Multipass>,
+    size: PhysicalSize<u32>,
+    format: TextureFormat,
+    depth_compare: CompareFunction,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+    instance_buffer: Buffer,
+    num_instances: u32,
+    depth_clear: f32,
+    shader_origin: ShaderOrigin,
+}
+
+impl Scene {
+    /// The depth test is baked into the pipeline, so `projection` fixes whether z is reversed.
+    pub fn new(
+        dev: &Device,
+        kind: PipelineKind,
+        size: PhysicalSize<u32>,
+        format: TextureFormat,
+        projection: &Projection,
+    ) -> Result<(Self, Uniform), PipelineError> {
+        Self::with_assets(dev, kind, size, format, projection, &SceneAssets::default())
+    }
+
+    /// Like `new`, with shaders and octree from `assets`.
+    pub fn with_assets(
+        dev: &Device,
+        kind: PipelineKind,
+        size: PhysicalSize<u32>,
+        format: TextureFormat,
+        projection: &Projection,
+        assets: &SceneAssets,
+    ) -> Result<(Self, Uniform), PipelineError> {
+        let (uniform, uniform_buffer) = create_uniform_buffer(dev, size);
+
+        let (positions, uv_coords, indices) = match kind {
+            PipelineKind::Mesh => (&CUBE_VERTEX_POSITIONS[..], &CUBE_UV_COORDS[..], &CUBE_INDICES[..]),
+            PipelineKind::Octree | PipelineKind::Shadertoy => {
+                (&QUAD_VERTEX_POSITIONS[..], &QUAD_UV_COORDS[..], &QUAD_INDICES[..])
+            }
+        };
+        let (vertex_buffer, index_buffer, num_indices) = create_polygon_buffers(
+            dev,
+            &positions
+                .iter()
+                .enumerate()
+                .map(|(i, &pos)| Vertex::new(pos, uv_coords[i]))
+                .collect::<Vec<Vertex>>(),
+            &Vec::from(indices),
+        );
+
+        // a few cubes with their own model matrices, the raymarcher draws its quad once
+        let instances = match kind {
+            PipelineKind::Mesh => vec![
+                Instance::new(Mat4::IDENTITY),
+                Instance::new(Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.5),
+                    Quat::from_rotation_y(45f32.to_radians()),
+                    Vec3::new(1.6, 0.0, 0.2),
+                )),
+                Instance::new(Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.35),
+                    Quat::from_euler(EulerRot::YXZ, 0.6, 0.4, 0.0),
+                    Vec3::new(0.3, 1.3, 0.3),
+                )),
+            ],
+            PipelineKind::Octree | PipelineKind::Shadertoy => vec![Instance::new(Mat4::IDENTITY)],
+        };
+        let (instance_buffer, num_instances) = create_instance_buffer(dev, &instances);
+
+        let demo;
+        let octree = match &assets.octree {
+            Some(octree) => octree,
+            None => {
+                demo = svo::demo_scene(SVO_DEPTH)?;
+                &demo
+            }
+        };
+        let svo = octree.serialize()?;
+        let max_binding_size = dev.limits().max_storage_buffer_binding_size as usize;
+        if svo.len() * 4 > max_binding_size {
+            return Err(PipelineError::Device(format!(
+                "the octree takes {} bytes, this device binds at most {} bytes per storage buffer.",
+                svo.len() * 4,
+                max_binding_size
+            )));
+        }
+        let svo_buffer = create_svo_buffer(dev, &svo);
+
+        let (audio_texture, audio_view, audio_sampler) = create_audio_texture(dev);
+        let (bind_group_layout, bind_group) =
+            create_buffer_descriptors(dev, &uniform_buffer, &svo_buffer, (&audio_view, &audio_sampler));
+
+        let depth_compare = projection.depth_compare();
+        let (pipeline, multipass) = create_pipeline(
+            dev,
+            kind,
+            &bind_group_layout,
+            &audio_texture,
+            format,
+            size,
+            depth_compare,
+            &assets.shaders,
+        )?;
+
+        let scene = Self {
+            kind,
+            uniform_buffer,
+            clear_color: CLEAR_COLOR,
+
+            audio_texture,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            multipass,
+            size,
+            format,
+            depth_compare,
+            vertex_buffer,
+            index_buffer,
+            num_indices,
+            instance_buffer,
+            num_instances,
+            depth_clear: projection.depth_clear(),
+            shader_origin: assets.shaders.clone(),
+        };
+
+        Ok((scene, uniform))
+    }
+
+    /// Rebuilds the pipeline from the shaders at `origin`, the current one stays on failure.
+    /// Shadertoy buffers start over.
+    pub fn reload_pipeline(&mut self, dev: &Device, origin: &ShaderOrigin) -> Result<(), PipelineError> {
+        (self.pipeline, self.multipass) = create_pipeline(
+            dev,
+            self.kind,
+            &self.bind_group_layout,
+            &self.audio_texture,
+            self.format,
+            self.size,
+            self.depth_compare,
+            origin,
+        )?;
+
+        Ok(())
+    }
+
+    /// The picture changes every frame without input, the mesh and shadertoy shaders run on the time.
+    pub fn is_animated(&self) -> bool {
+        matches!(self.kind, PipelineKind::Mesh | PipelineKind::Shadertoy)
+    }
+
+    /// Uploads `Analyzer::texels`, shaders see silence as black until the first call.
+    pub fn write_audio(&self, queue: &Queue, texels: &[u8]) {
+        write_audio_texture(queue, &self.audio_texture, texels);
+    }
+
+    /// Only shadertoy buffers depend on the size, they are cleared.
+    pub fn resize(&mut self, dev: &Device, size: PhysicalSize<u32>) {
+        self.size = size;
+        if let Some(multipass) = self.multipass.as_mut() {
+            multipass.resize(dev, size);
+        }
+    }
+
+    /// Where the pipeline was first built from.
+    pub fn shader_origin(&self) -> &ShaderOrigin {
+        &self.shader_origin
+    }
+
+    /// Files the pipeline is built from, relative to the crate root.
+    pub fn shader_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.kind.shader_files().iter().map(|file| file.path.to_string()).collect();
+        if let Some(multipass) = &self.multipass {
+            paths.extend(multipass.files());
+        }
+
+        paths
+    }
+
+    /// Camera the scene starts with, inspecting the cube at [0, 1] from outside
+    /// or flying in front of the octree at [1, 2]. Shadertoy ignores it.
+    pub fn default_camera(kind: PipelineKind) -> Camera {
+        match kind {
+            PipelineKind::Mesh | PipelineKind::Shadertoy => Camera::new(CameraMode::Orbit, Vec3::new(2.0, 1.8, -1.5), Vec3::splat(0.5)),
+            PipelineKind::Octree => Camera::new(CameraMode::Fly, Vec3::new(1.5, 1.5, -0.5), Vec3::splat(1.5)),
+        }
+    }
+
+    /// Clears `view` and `depth_view` and draws the scene into them.
+    pub fn render(&mut self, encoder: &mut CommandEncoder, view: &TextureView, depth_view: &TextureView) {
+        if let Some(multipass) = &self.multipass {
+            multipass.render_buffers(encoder, &self.bind_group);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("scene_render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.depth_clear),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        // the triangle comes from the vertex index
+        if let Some(multipass) = self.multipass.as_mut() {
+            render_pass.set_bind_group(1, multipass.image_bind_group(), &[]);
+            render_pass.draw(0..3, 0..1);
+            drop(render_pass);
+            multipass.finish_frame();
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if self.kind == PipelineKind::Mesh {
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        }
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    dev: &Device,
+    kind: PipelineKind,
+    bind_group_layout: &BindGroupLayout,
+    audio_texture: &Texture,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+    depth_compare: CompareFunction,
+    origin: &ShaderOrigin,
+) -> Result<(RenderPipeline, Option<Multipass>), PipelineError> {
+    match kind {
+        PipelineKind::Mesh => {
+            create_main_pipeline(dev, bind_group_layout, format, depth_compare, origin).map(|p| (p, None))
+        }
+        PipelineKind::Octree => create_octree_pipeline(dev, bind_group_layout, format, origin).map(|p| (p, None)),
+        PipelineKind::Shadertoy => {
+            let (multipass, pipeline) = Multipass::new(dev, bind_group_layout, audio_texture, format, size, origin)?;
+            Ok((pipeline, Some(multipass)))
+        }
+    }
+}
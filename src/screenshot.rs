@@ -0,0 +1,175 @@
+use crate::offscreen::{copy_to_buffer, create_readback_buffer, read_buffer, save_png};
+use log::{error, info};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wgpu::{Buffer, CommandEncoder, Device, Texture, TextureFormat, TextureUsages};
+use winit::dpi::PhysicalSize;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Set from the hotkey or the ui, consumed by the next redraw.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ScreenshotRequest {
+    pub pending: bool,
+    pub include_overlay: bool,
+}
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    UnsupportedFormat(TextureFormat),
+    NotCopyable,
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::UnsupportedFormat(format) => {
+                write!(f, "can't take screenshots of {format:?} frames.")
+            }
+            ScreenshotError::NotCopyable => {
+                write!(f, "the surface doesn't allow copies, screenshots are unavailable.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+/// Converts rows of a swapchain texture to rgba8 in place.
+///
+/// Srgb formats already store encoded values and the others are shown as they are,
+/// so only the channel order changes. Alpha is dropped, the window is opaque.
+pub fn to_rgba8(pixels: &mut [u8], format: TextureFormat) -> Result<(), ScreenshotError> {
+    let bgra = match format {
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        _ => return Err(ScreenshotError::UnsupportedFormat(format)),
+    };
+
+    for pixel in pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize) {
+        if bgra {
+            pixel.swap(0, 2);
+        }
+        pixel[3] = 255;
+    }
+
+    Ok(())
+}
+
+// days since the unix epoch to a utc (year, month, day)
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+    (yoe + era * 400 + (month <= 2) as i64, month as u32, day as u32)
+}
+
+/// `<utc date>_<utc time>`, with milliseconds so quick presses don't collide.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}-{:03}",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// `screenshot_<utc date>_<utc time>.png`.
+pub fn timestamped_filename(time: SystemTime) -> String {
+    format!("screenshot_{}.png", timestamp(time))
+}
+
+/// Copy of one swapchain frame on its way to a png.
+pub struct Capture {
+    buffer: Buffer,
+    size: PhysicalSize<u32>,
+    format: TextureFormat,
+}
+
+impl Capture {
+    /// The surface has to be configured with `TextureUsages::COPY_SRC`.
+    pub fn new(dev: &Device, texture: &Texture) -> Result<Self, ScreenshotError> {
+        if !texture.usage().contains(TextureUsages::COPY_SRC) {
+            return Err(ScreenshotError::NotCopyable);
+        }
+
+        let format = texture.format();
+        to_rgba8(&mut [], format)?;
+
+        let size = PhysicalSize::new(texture.width(), texture.height());
+
+        Ok(Self {
+            buffer: create_readback_buffer(dev, size, BYTES_PER_PIXEL),
+            size,
+            format,
+        })
+    }
+
+    /// Records the copy, before the imgui pass to leave out the overlay or after it to keep it.
+    pub fn record(&self, encoder: &mut CommandEncoder, texture: &Texture) {
+        copy_to_buffer(encoder, texture, &self.buffer, BYTES_PER_PIXEL);
+    }
+
+    /// Waits for the submitted copy, the png is written on a separate thread.
+    pub fn save(self, dev: &Device, dir: &Path) {
+        let mut pixels = read_buffer(dev, &self.buffer, self.size, BYTES_PER_PIXEL);
+        let path: PathBuf = dir.join(timestamped_filename(SystemTime::now()));
+        let (size, format) = (self.size, self.format);
+
+        thread::spawn(move || {
+            to_rgba8(&mut pixels, format).expect("format was checked on capture.");
+            match save_png(&path, size, &pixels) {
+                Ok(()) => info!("saved screenshot {}.", path.display()),
+                Err(e) => error!("failed to save screenshot {}: {}", path.display(), e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn swizzles_bgra() {
+        let mut pixels = [10, 20, 30, 0, 40, 50, 60, 128];
+        to_rgba8(&mut pixels, TextureFormat::Bgra8UnormSrgb).unwrap();
+        assert_eq!(pixels, [30, 20, 10, 255, 60, 50, 40, 255]);
+
+        let mut pixels = [10, 20, 30, 0];
+        to_rgba8(&mut pixels, TextureFormat::Rgba8Unorm).unwrap();
+        assert_eq!(pixels, [10, 20, 30, 255]);
+
+        assert!(matches!(
+            to_rgba8(&mut [], TextureFormat::Rgba16Float),
+            Err(ScreenshotError::UnsupportedFormat(TextureFormat::Rgba16Float))
+        ));
+    }
+
+    #[test]
+    fn filename_is_utc_timestamp() {
+        assert_eq!(timestamped_filename(UNIX_EPOCH), "screenshot_1970-01-01_00-00-00-000.png");
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_042);
+        assert_eq!(timestamped_filename(time), "screenshot_2023-11-14_22-13-20-042.png");
+
+        // leap day
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(timestamped_filename(time), "screenshot_2000-02-29_00-00-00-000.png");
+    }
+}
@@ -3,6 +3,12 @@
 layout(location = 0) in vec3 in_pos;
 layout(location = 1) in vec2 in_uv;
 
+// per instance model matrix, one column per location
+layout(location = 2) in vec4 in_model_0;
+layout(location = 3) in vec4 in_model_1;
+layout(location = 4) in vec4 in_model_2;
+layout(location = 5) in vec4 in_model_3;
+
 layout(location = 0) out vec4 fs_pos;
 layout(location = 1) out vec2 fs_uv;
 
@@ -16,7 +22,8 @@ layout (binding = 0) uniform UBO {
 } ubo;
 
 void main() {
-    gl_Position = ubo.proj * vec4(in_pos, 1.0);
+    mat4 model = mat4(in_model_0, in_model_1, in_model_2, in_model_3);
+    gl_Position = ubo.proj * model * vec4(in_pos, 1.0);
 
     fs_pos = gl_Position;
     fs_uv = in_uv;
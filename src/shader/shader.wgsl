@@ -1,12 +1,12 @@
 struct Uniform {
-    view_proj: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    res: vec2<u32>,
+    mouse: vec2<f32>,
+    time: u32,
     cam_pos: vec4<f32>,
     cam_dir: vec4<f32>,
     cam_plane_u: vec4<f32>,
     cam_plane_v: vec4<f32>,
-    res: vec2<u32>,
-    mouse: vec2<f32>,
-    time: u32,
 };
 
 @group(0) @binding(0)
@@ -68,12 +68,17 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         &iter,
     );
 
-    if (mat_info == 3u) {
-        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    if (!result || mat_info == 0u) {
+        return vec4<f32>(0.1, 0.2, 0.3, 1.0);
     }
 
+    // simple directional light, material id picks the albedo
+    let light: vec3<f32> = normalize(vec3<f32>(0.4, 1.0, -0.6));
+    let albedo: vec3<f32> = vec3<f32>(0.5) + 0.5 * cos(f32(mat_info) + vec3<f32>(0.0, 2.0, 4.0));
+    let diffuse: f32 = max(dot(norm, light), 0.0);
+
     //return vec4<f32>(vec4<f32>(f32(iter) / 100.0) + 0.5 * vec4<f32>(f32(mat_info == 1), f32(mat_info == 2), 1.0, 1.0));
-    return vec4<f32>(f32(mat_info == 1));
+    return vec4<f32>(albedo * (0.25 + 0.75 * diffuse), 1.0);
 }
 
 fn raymarch_leaf(
@@ -123,8 +128,8 @@ fn raymarch_leaf(
     var h: f32 = t_max;
 
     // initialize current voxel to first child of root
-    var parent: u32 = 0u; // first child index
-    var cur: u32 = svo[0]; // the current selected node, init with root
+    var parent: u32 = 0u; // index of the parent descriptor, root is at 0
+    var cur: u32 = 0u; // cached parent descriptor, 0 if not fetched yet
     var pos: vec3<f32> = vec3<f32>(1.0);
     var idx: u32 = 0u; // child octant index
 
@@ -149,17 +154,10 @@ fn raymarch_leaf(
     while (scale < STACK_SIZE && scale > 0) {
         iter++;
 
-        // fetch child descriptor unless it is already valid
+        // fetch parent descriptor unless it is already valid
         if (cur == 0u) {
             // READ
-            cur = svo[parent & 0xFFFFFFu + (idx ^ oct_mask)];
-        }
-
-        // leaf node
-        if ((cur & 0xFF000000u) == 0) {
-            //*p_mat = cur & 0xFFFFFFu;
-            *p_mat = 3u;
-            break;
+            cur = svo[parent];
         }
 
         // determine maximum t-value of the cube by
@@ -169,8 +167,17 @@ fn raymarch_leaf(
 
         // process voxel if the corresponding bit in
         // if valid mask is set and the active t-span is non-empty
-        let child_shift: u32 = 8 - (idx ^ oct_mask);
+        let child_shift: u32 = 7u - (idx ^ oct_mask);
         if (((cur << child_shift) & 0x80000000u) != 0 && t_min <= t_max) {
+            let child: u32 = (cur & 0xFFFFFFu) + (idx ^ oct_mask);
+            let child_desc: u32 = svo[child];
+
+            // leaf node, the child has no valid mask
+            if ((child_desc & 0xFF000000u) == 0) {
+                *p_mat = child_desc & 0xFFFFFFu;
+                break;
+            }
+
             // INTERSECT
             // intersect active t-span with the cube and evaluate
             // tx(), ty(), tz() at the center of the voxel
@@ -183,7 +190,7 @@ fn raymarch_leaf(
             }
             h = tc_max;
 
-            parent = cur;
+            parent = child;
 
             // select child voxel that the ray enters first
             idx = 0u;
@@ -202,7 +209,7 @@ fn raymarch_leaf(
                 pos.z += scale_exp2;
             }
 
-            cur = 0u;
+            cur = child_desc;
 
             continue;
         }
@@ -245,12 +252,11 @@ fn raymarch_leaf(
             }
 
             if (differing_bits == 0) {
-                *p_mat = 3u;
                 return false;
             }
 
             // position of the highest differing bit
-            scale = (differing_bits);
+            scale = firstLeadingBit(differing_bits);
 
             scale_exp2 = bitcast<f32>((scale - STACK_SIZE + 127u) << 23u); // exp2f(scale - s_max)
 
@@ -275,10 +281,10 @@ fn raymarch_leaf(
 
     var t_corner: vec3<f32> = t_coef * (pos + scale_exp2) - t_bias;
 
-    var norm: vec3<f32> = select(vec3<f32>(-1, 0, 0),
-        select(vec3<f32>(0, -1, 0),
-        vec3<f32>(0, 0, -1),
-        t_corner.y > t_corner.z),
+    // the ray enters through the face it reaches last, select(false, true, cond)
+    var norm: vec3<f32> = select(
+        select(vec3<f32>(0, 0, -1), vec3<f32>(0, -1, 0), t_corner.y > t_corner.z),
+        vec3<f32>(-1, 0, 0),
         t_corner.x > t_corner.y && t_corner.x > t_corner.z);
 
     // undo mirroring of the coordinate system
@@ -0,0 +1,19 @@
+// reads its own last frame from iChannel0, the trail fades while a dot moves along,
+// dragging with the left mouse button paints into it
+
+float stamp(vec2 fragCoord, vec2 center) {
+    return smoothstep(12.0, 8.0, length(fragCoord - center));
+}
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    float trail = texture(iChannel0, uv).r * exp(-iTimeDelta * 1.5);
+
+    vec2 dot_pos = iResolution.xy * (0.5 + 0.35 * vec2(cos(iTime * 1.3), sin(iTime * 1.9)));
+    trail = max(trail, stamp(fragCoord, dot_pos));
+    if (iMouse.z > 0.0) {
+        trail = max(trail, stamp(fragCoord, iMouse.xy));
+    }
+
+    fragColor = vec4(trail, 0.0, 0.0, 1.0);
+}
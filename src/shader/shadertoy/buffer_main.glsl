@@ -0,0 +1,13 @@
+
+// buffers are float targets read back by other passes, nothing is encoded.
+// rows are stored bottom up like in gl, so texture coordinates and texelFetch line up with fragCoord
+void main() {
+    for (int i = 0; i < 4; i++) {
+        iChannelResolution[i] = channel_resolution[i].xyz;
+    }
+
+    vec4 color = vec4(0.0);
+    mainImage(color, gl_FragCoord.xy);
+
+    shadertoy_color = color;
+}
@@ -0,0 +1,12 @@
+// paste a shadertoy image shader here, or list more passes in project.toml,
+// everything is reloaded on save
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    vec3 col = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0, 2, 4));
+    // brighter on every beat of the audio input
+    col *= 1.0 + 0.5 * ubo.beat;
+
+    float trail = texture(iChannel0, uv).r;
+    fragColor = vec4(mix(col * 0.35, vec3(1.0), trail), 1.0);
+}
@@ -0,0 +1,14 @@
+
+// shadertoy puts the origin in the bottom left corner and shows its output as is,
+// our targets are srgb, so the color is decoded to come out unchanged
+void main() {
+    for (int i = 0; i < 4; i++) {
+        iChannelResolution[i] = channel_resolution[i].xyz;
+    }
+
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(color, vec2(gl_FragCoord.x, float(ubo.res.y) - gl_FragCoord.y));
+
+    vec3 c = clamp(color.rgb, 0.0, 1.0);
+    shadertoy_color = vec4(mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c)), 1.0);
+}
@@ -0,0 +1,61 @@
+#version 450
+
+layout (location = 0) out vec4 shadertoy_color;
+
+layout (binding = 0) uniform UBO {
+    mat4 proj;
+
+    uvec2 res;
+    vec2 mouse;
+
+    uint time;
+
+    vec4 cam_pos;
+    vec4 cam_dir;
+    vec4 cam_plane_u;
+    vec4 cam_plane_v;
+
+    vec4 mouse_click;
+    vec4 date;
+    float time_delta;
+    uint frame;
+
+    float beat;
+    float bpm;
+    float bass;
+    float mid;
+    float treble;
+} ubo;
+
+// spectrum at y = 0.25 and waveform at y = 0.75, 512 texels each
+layout (binding = 2) uniform texture2D audio_texture;
+layout (binding = 3) uniform sampler audio_sampler;
+
+// buffers or the audio bound in the project file, unbound channels are black
+layout (set = 1, binding = 0) uniform texture2D channel0_texture;
+layout (set = 1, binding = 1) uniform texture2D channel1_texture;
+layout (set = 1, binding = 2) uniform texture2D channel2_texture;
+layout (set = 1, binding = 3) uniform texture2D channel3_texture;
+layout (set = 1, binding = 4) uniform sampler channel0_sampler;
+layout (set = 1, binding = 5) uniform sampler channel1_sampler;
+layout (set = 1, binding = 6) uniform sampler channel2_sampler;
+layout (set = 1, binding = 7) uniform sampler channel3_sampler;
+layout (set = 1, binding = 8) uniform Channels {
+    vec4 channel_resolution[4];
+};
+
+// shadertoy's is a vec3 array, the block keeps std140's vec4s. filled in before mainImage runs
+vec3 iChannelResolution[4];
+
+#define iResolution vec3(vec2(ubo.res), 1.0)
+#define iTime (float(ubo.time) * 0.001)
+#define iTimeDelta ubo.time_delta
+#define iFrame int(ubo.frame)
+#define iMouse ubo.mouse_click
+#define iDate ubo.date
+#define iChannel0 sampler2D(channel0_texture, channel0_sampler)
+#define iChannel1 sampler2D(channel1_texture, channel1_sampler)
+#define iChannel2 sampler2D(channel2_texture, channel2_sampler)
+#define iChannel3 sampler2D(channel3_texture, channel3_sampler)
+#define iAudio sampler2D(audio_texture, audio_sampler)
+
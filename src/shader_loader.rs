@@ -0,0 +1,310 @@
+//! Compiles glsl and wgsl sources with naga, so no spir-v has to be built ahead of time.
+//!
+//! Only depends on naga, the build script includes this file to check the shaders
+//! when the `shader-check` feature is enabled.
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{Module, ShaderStage};
+use std::error::Error;
+use std::fmt;
+use std::borrow::Cow;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Glsl(ShaderStage),
+    Wgsl,
+    /// glsl fragment shader defining shadertoy's `mainImage`, see `SHADERTOY_PRELUDE`
+    Shadertoy,
+    /// same for a buffer pass, the color is stored as is
+    ShadertoyBuffer,
+}
+
+/// Shader source file, `path` is relative to the crate root.
+#[derive(Copy, Clone, Debug)]
+pub struct ShaderFile {
+    pub path: &'static str,
+    pub language: ShaderLanguage,
+    /// contents at build time
+    pub embedded: &'static str,
+}
+
+pub const MESH_VERT: ShaderFile = ShaderFile {
+    path: "src/shader/glsl/shader.vert",
+    language: ShaderLanguage::Glsl(ShaderStage::Vertex),
+    embedded: include_str!("shader/glsl/shader.vert"),
+};
+
+pub const MESH_FRAG: ShaderFile = ShaderFile {
+    path: "src/shader/glsl/shader.frag",
+    language: ShaderLanguage::Glsl(ShaderStage::Fragment),
+    embedded: include_str!("shader/glsl/shader.frag"),
+};
+
+pub const OCTREE_WGSL: ShaderFile = ShaderFile {
+    path: "src/shader/shader.wgsl",
+    language: ShaderLanguage::Wgsl,
+    embedded: include_str!("shader/shader.wgsl"),
+};
+
+pub const FULLSCREEN_VERT: ShaderFile = ShaderFile {
+    path: "src/shader/glsl/fullscreen.vert",
+    language: ShaderLanguage::Glsl(ShaderStage::Vertex),
+    embedded: include_str!("shader/glsl/fullscreen.vert"),
+};
+
+// the passes of the shipped shadertoy project
+pub const SHADERTOY_BUFFER_A: ShaderFile = ShaderFile {
+    path: "src/shader/shadertoy/buffer_a.glsl",
+    language: ShaderLanguage::ShadertoyBuffer,
+    embedded: include_str!("shader/shadertoy/buffer_a.glsl"),
+};
+
+pub const SHADERTOY_IMAGE: ShaderFile = ShaderFile {
+    path: "src/shader/shadertoy/image.glsl",
+    language: ShaderLanguage::Shadertoy,
+    embedded: include_str!("shader/shadertoy/image.glsl"),
+};
+
+pub const SHADER_FILES: [ShaderFile; 6] = [
+    MESH_VERT,
+    MESH_FRAG,
+    OCTREE_WGSL,
+    FULLSCREEN_VERT,
+    SHADERTOY_BUFFER_A,
+    SHADERTOY_IMAGE,
+];
+
+/// Declares the uniform block and the channels and maps shadertoy's inputs onto them.
+pub const SHADERTOY_PRELUDE: &str = include_str!("shader/shadertoy/prelude.glsl");
+/// Calls `mainImage` and writes its color to the screen.
+pub const SHADERTOY_MAIN: &str = include_str!("shader/shadertoy/main.glsl");
+/// Calls `mainImage` and writes its color to a buffer.
+pub const SHADERTOY_BUFFER_MAIN: &str = include_str!("shader/shadertoy/buffer_main.glsl");
+
+fn read_error(path: &str, message: String) -> ShaderError {
+    ShaderError {
+        path: path.to_string(),
+        diagnostics: vec![Diagnostic {
+            line: 0,
+            column: 0,
+            message,
+        }],
+    }
+}
+
+/// Contents of the file at `path`, `Embedded` only has the files in `SHADER_FILES`.
+pub fn read_source(path: &str, origin: &ShaderOrigin) -> Result<Cow<'static, str>, ShaderError> {
+    match origin {
+        ShaderOrigin::Embedded => SHADER_FILES
+            .iter()
+            .find(|file| file.path == path)
+            .map(|file| Cow::Borrowed(file.embedded))
+            .ok_or_else(|| read_error(path, "not embedded in the binary.".to_string())),
+        ShaderOrigin::Disk(root) => fs::read_to_string(root.join(path))
+            .map(Cow::Owned)
+            .map_err(|e| read_error(path, e.to_string())),
+    }
+}
+
+/// Where pipelines read their shader sources from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShaderOrigin {
+    /// the sources compiled into the binary
+    #[default]
+    Embedded,
+    /// the crate root the shader paths are relative to, used to pick up edits while running
+    Disk(PathBuf),
+}
+
+impl ShaderFile {
+    pub fn compile(&self) -> Result<Module, ShaderError> {
+        compile(self.path, self.embedded, self.language)
+    }
+
+    /// Like `compile`, but reads the source from `origin`, a missing file is reported as a diagnostic.
+    pub fn load(&self, origin: &ShaderOrigin) -> Result<Module, ShaderError> {
+        match origin {
+            ShaderOrigin::Embedded => self.compile(),
+            ShaderOrigin::Disk(_) => compile(self.path, &read_source(self.path, origin)?, self.language),
+        }
+    }
+}
+
+/// A single problem, line and column are 1-based and 0 when naga gives no location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    pub path: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}:{}:{}: {}", self.path, diagnostic.line, diagnostic.column, diagnostic.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ShaderError {}
+
+fn diagnostic(location: Option<naga::SourceLocation>, error: &dyn Error) -> Diagnostic {
+    // naga nests the actual cause, e.g. function -> expression -> type mismatch
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    Diagnostic {
+        line: location.map_or(0, |l| l.line_number),
+        column: location.map_or(0, |l| l.line_position),
+        message,
+    }
+}
+
+/// Parses and validates `source`, errors point into the file at `path`.
+pub fn compile(path: &str, source: &str, language: ShaderLanguage) -> Result<Module, ShaderError> {
+    let error = |diagnostics| ShaderError {
+        path: path.to_string(),
+        diagnostics,
+    };
+
+    let module = match language {
+        ShaderLanguage::Shadertoy => return compile_shadertoy(path, source, SHADERTOY_MAIN),
+        ShaderLanguage::ShadertoyBuffer => return compile_shadertoy(path, source, SHADERTOY_BUFFER_MAIN),
+        ShaderLanguage::Glsl(stage) => naga::front::glsl::Frontend::default()
+            .parse(&naga::front::glsl::Options::from(stage), source)
+            .map_err(|errors| {
+                error(
+                    errors
+                        .iter()
+                        .map(|e| {
+                            let location = e.meta.is_defined().then(|| e.meta.location(source));
+                            diagnostic(location, e)
+                        })
+                        .collect(),
+                )
+            })?,
+        ShaderLanguage::Wgsl => naga::front::wgsl::parse_str(source).map_err(|e| {
+            error(vec![Diagnostic {
+                line: e.location(source).map_or(0, |l| l.line_number),
+                column: e.location(source).map_or(0, |l| l.line_position),
+                message: e.message().to_string(),
+            }])
+        })?,
+    };
+
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|e| error(vec![diagnostic(e.location(source), e.as_inner())]))?;
+
+    Ok(module)
+}
+
+// wraps `source` in the prelude and `main`, diagnostics are moved back to lines of `source`
+// and the ones outside of it end up at line 0
+fn compile_shadertoy(path: &str, source: &str, main: &str) -> Result<Module, ShaderError> {
+    let composed = format!("{SHADERTOY_PRELUDE}{source}\n{main}");
+    let first_line = SHADERTOY_PRELUDE.lines().count() as u32 + 1;
+    let last_line = first_line + source.lines().count() as u32;
+
+    compile(path, &composed, ShaderLanguage::Glsl(ShaderStage::Fragment)).map_err(|mut error| {
+        for diagnostic in &mut error.diagnostics {
+            if (first_line..last_line).contains(&diagnostic.line) {
+                diagnostic.line -= first_line - 1;
+            } else {
+                diagnostic.line = 0;
+                diagnostic.column = 0;
+            }
+        }
+        error
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_shaders_compile() {
+        for file in SHADER_FILES {
+            if let Err(e) = file.compile() {
+                panic!("{e}");
+            }
+        }
+    }
+
+    #[test]
+    fn glsl_error_has_location() {
+        let source = "#version 450\nvoid main() {\n    float x = undefined_name;\n}\n";
+        let error = compile("broken.frag", source, ShaderLanguage::Glsl(ShaderStage::Fragment)).unwrap_err();
+
+        assert_eq!(error.path, "broken.frag");
+        assert_eq!(error.diagnostics[0].line, 3);
+        assert!(error.to_string().starts_with("broken.frag:3:"), "{error}");
+    }
+
+    #[test]
+    fn wgsl_errors_have_location() {
+        let source = "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0) +;\n}\n";
+        let error = compile("parse.wgsl", source, ShaderLanguage::Wgsl).unwrap_err();
+        assert_eq!(error.diagnostics[0].line, 3);
+
+        // parses, but returns the wrong type
+        let source = "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return 1.0;\n}\n";
+        let error = compile("invalid.wgsl", source, ShaderLanguage::Wgsl).unwrap_err();
+        assert_eq!(error.diagnostics.len(), 1);
+        assert!(error.diagnostics[0].line > 0, "{error}");
+        assert!(error.to_string().starts_with("invalid.wgsl:"), "{error}");
+    }
+
+    #[test]
+    fn shadertoy_inputs_have_shadertoy_types() {
+        let source = "void mainImage(out vec4 c, in vec2 p) {\n    vec3 r = iChannelResolution[1];\n    c = vec4(r / iResolution, 1.0);\n}\n";
+        for language in [ShaderLanguage::Shadertoy, ShaderLanguage::ShadertoyBuffer] {
+            if let Err(e) = compile("toy.glsl", source, language) {
+                panic!("{e}");
+            }
+        }
+    }
+
+    #[test]
+    fn shadertoy_errors_point_into_source() {
+        let source = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {\n    fragColor = vec4(iTime, iMouse.xy, undefined_name);\n}\n";
+        let error = compile("toy.glsl", source, ShaderLanguage::Shadertoy).unwrap_err();
+        assert_eq!(error.diagnostics[0].line, 2, "{error}");
+
+        // without `mainImage` the error is in the appended main
+        let error = compile("empty.glsl", "", ShaderLanguage::Shadertoy).unwrap_err();
+        assert_eq!(error.diagnostics[0].line, 0, "{error}");
+    }
+
+    #[test]
+    fn loads_from_disk() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        for file in SHADER_FILES {
+            let module = file.load(&ShaderOrigin::Disk(root.clone())).unwrap();
+            assert_eq!(module.entry_points.len(), file.compile().unwrap().entry_points.len());
+        }
+
+        let error = MESH_FRAG.load(&ShaderOrigin::Disk(root.join("missing"))).unwrap_err();
+        assert_eq!(error.path, MESH_FRAG.path);
+        assert_eq!(error.diagnostics[0].line, 0);
+    }
+}
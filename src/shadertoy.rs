@@ -0,0 +1,120 @@
+//! Shadertoy inputs that are tracked across frames, the shader side is `SHADERTOY_PRELUDE`.
+
+use crate::screenshot::civil_from_days;
+use crate::uniform::Uniform;
+use std::time::{SystemTime, UNIX_EPOCH};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseButton};
+
+/// `iDate`, year, zero based month, day and seconds since midnight, in utc.
+pub fn date(time: SystemTime) -> [f32; 4] {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400) as f64 + since_epoch.subsec_micros() as f64 / 1e6;
+
+    [year as f32, (month - 1) as f32, day as f32, secs_of_day as f32]
+}
+
+/// Frame counter and left mouse button, reported the way shadertoy's `iMouse` does.
+#[derive(Clone, Debug, Default)]
+pub struct ShadertoyInputs {
+    pub frame: u32,
+    // window pixels with the origin in the top left corner
+    cursor: [f32; 2],
+    pressed: bool,
+    // the button went down since the last frame
+    clicked: bool,
+    drag: [f32; 2],
+    click: Option<[f32; 2]>,
+}
+
+impl ShadertoyInputs {
+    pub fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor = [position.x as f32, position.y as f32];
+        if self.pressed {
+            self.drag = self.cursor;
+        }
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        self.pressed = state == ElementState::Pressed;
+        if self.pressed {
+            self.clicked = true;
+            self.drag = self.cursor;
+            self.click = Some(self.cursor);
+        }
+    }
+
+    /// Writes `mouse`, `mouse_click`, `date`, `time_delta` and `frame` and moves on to the next frame.
+    /// Expects `uniform.res` to be current, shadertoy's origin is the bottom left corner.
+    pub fn write_uniform(&mut self, uniform: &mut Uniform, time_delta: f32, now: SystemTime) {
+        let height = uniform.res[1] as f32;
+        let flip = |[x, y]: [f32; 2]| [x, height - y];
+
+        uniform.mouse = self.cursor;
+        // xy is the last position while pressed, z is negative once released
+        // and w only positive in the frame of the click
+        uniform.mouse_click = match self.click.map(flip) {
+            Some([click_x, click_y]) => {
+                let [x, y] = flip(self.drag);
+                let z = if self.pressed { click_x } else { -click_x };
+                let w = if self.clicked { click_y } else { -click_y };
+                [x, y, z, w]
+            }
+            None => [0.0; 4],
+        };
+        uniform.date = date(now);
+        uniform.time_delta = time_delta;
+        uniform.frame = self.frame;
+
+        self.clicked = false;
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use winit::dpi::PhysicalPosition;
+
+    #[test]
+    fn mouse_follows_shadertoy() {
+        let mut inputs = ShadertoyInputs::default();
+        let mut uniform = Uniform { res: [100, 50], ..Default::default() };
+        let now = UNIX_EPOCH;
+
+        inputs.write_uniform(&mut uniform, 0.0, now);
+        assert_eq!(uniform.mouse_click, [0.0; 4]);
+
+        inputs.handle_cursor_moved(PhysicalPosition::new(10.0, 5.0));
+        inputs.handle_mouse_button(MouseButton::Left, ElementState::Pressed);
+        inputs.write_uniform(&mut uniform, 0.0, now);
+        assert_eq!(uniform.mouse_click, [10.0, 45.0, 10.0, 45.0]);
+
+        inputs.handle_cursor_moved(PhysicalPosition::new(30.0, 20.0));
+        inputs.write_uniform(&mut uniform, 0.0, now);
+        assert_eq!(uniform.mouse_click, [30.0, 30.0, 10.0, -45.0]);
+
+        // moving after the release leaves xy where the drag ended
+        inputs.handle_mouse_button(MouseButton::Left, ElementState::Released);
+        inputs.handle_cursor_moved(PhysicalPosition::new(80.0, 0.0));
+        inputs.write_uniform(&mut uniform, 0.0, now);
+        assert_eq!(uniform.mouse_click, [30.0, 30.0, -10.0, -45.0]);
+        assert_eq!(uniform.mouse, [80.0, 0.0]);
+        assert_eq!(uniform.frame, 3);
+    }
+
+    #[test]
+    fn date_is_utc() {
+        assert_eq!(date(UNIX_EPOCH), [1970.0, 0.0, 1.0, 0.0]);
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        assert_eq!(date(time), [2023.0, 10.0, 14.0, 80_000.5]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Project file describing a shadertoy with up to four buffers and the image pass,
+//! see `src/shader/shadertoy/project.toml` for the format.
+
+use crate::config::parse_toml;
+use crate::shader_loader::{compile, read_source, ShaderError, ShaderLanguage, ShaderOrigin};
+use naga::Module;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use toml::{Table, Value};
+
+pub const SHADERTOY_DIR: &str = "src/shader/shadertoy";
+pub const PROJECT_FILE: &str = "src/shader/shadertoy/project.toml";
+const EMBEDDED_PROJECT: &str = include_str!("shader/shadertoy/project.toml");
+
+pub const CHANNELS: usize = 4;
+
+/// Passes in the order they run each frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PassName {
+    BufferA,
+    BufferB,
+    BufferC,
+    BufferD,
+    Image,
+}
+
+impl PassName {
+    pub const ALL: [PassName; 5] = [
+        PassName::BufferA,
+        PassName::BufferB,
+        PassName::BufferC,
+        PassName::BufferD,
+        PassName::Image,
+    ];
+
+    /// Table name in the project file.
+    pub fn key(&self) -> &'static str {
+        match self {
+            PassName::BufferA => "buffer_a",
+            PassName::BufferB => "buffer_b",
+            PassName::BufferC => "buffer_c",
+            PassName::BufferD => "buffer_d",
+            PassName::Image => "image",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|name| name.key() == key)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Wrap {
+    #[default]
+    Clamp,
+    Repeat,
+}
+
+/// What a channel reads, `audio` is shadertoy's audio texture, see `spectrum`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Buffer(PassName),
+    Audio,
+}
+
+/// A buffer or the audio bound to `iChannelN` of a pass.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Channel {
+    pub input: Input,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pass {
+    pub name: PassName,
+    /// relative to `SHADERTOY_DIR`
+    pub source: String,
+    pub channels: [Option<Channel>; CHANNELS],
+}
+
+impl Pass {
+    pub fn source_path(&self) -> String {
+        format!("{SHADERTOY_DIR}/{}", self.source)
+    }
+
+    pub fn compile(&self, origin: &ShaderOrigin) -> Result<Module, ShaderError> {
+        let path = self.source_path();
+        let language = match self.name {
+            PassName::Image => ShaderLanguage::Shadertoy,
+            _ => ShaderLanguage::ShadertoyBuffer,
+        };
+
+        compile(&path, &read_source(&path, origin)?, language)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectError(pub String);
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PROJECT_FILE}: {}", self.0)
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+/// The passes to run, sorted by `PassName` and always ending with the image. A lone image pass
+/// without channels gets the audio on `iChannel0`, like a single pass shadertoy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Project {
+    pub passes: Vec<Pass>,
+}
+
+// `name` is the table's dotted name, for the errors
+fn string<'a>(table: &'a Table, name: &str, key: &str) -> Result<Option<&'a str>, ProjectError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(value) => Err(ProjectError(format!("[{name}] {key} has to be a string, not a {}.", value.type_str()))),
+    }
+}
+
+fn table<'a>(value: &'a Value, name: &str) -> Result<&'a Table, ProjectError> {
+    value
+        .as_table()
+        .ok_or_else(|| ProjectError(format!("[{name}] has to be a table, not a {}.", value.type_str())))
+}
+
+fn parse_channel(table: &Table, name: &str) -> Result<Channel, ProjectError> {
+    if let Some(key) = table.keys().find(|key| !["input", "filter", "wrap"].contains(&key.as_str())) {
+        return Err(ProjectError(format!("unknown key {key} in [{name}], expected one of input, filter, wrap.")));
+    }
+
+    let input = string(table, name, "input")?.ok_or_else(|| ProjectError(format!("[{name}] needs an input.")))?;
+    let input = match input {
+        "audio" => Input::Audio,
+        input => PassName::from_key(input)
+            .filter(|name| *name != PassName::Image)
+            .map(Input::Buffer)
+            .ok_or_else(|| ProjectError(format!("[{name}] unknown input {input}, expected buffer_a to buffer_d or audio.")))?,
+    };
+
+    let filter = match string(table, name, "filter")? {
+        None => Filter::default(),
+        Some("nearest") => Filter::Nearest,
+        Some("linear") => Filter::Linear,
+        Some(other) => return Err(ProjectError(format!("[{name}] unknown filter {other}, expected nearest or linear."))),
+    };
+    let wrap = match string(table, name, "wrap")? {
+        None => Wrap::default(),
+        Some("clamp") => Wrap::Clamp,
+        Some("repeat") => Wrap::Repeat,
+        Some(other) => return Err(ProjectError(format!("[{name}] unknown wrap {other}, expected clamp or repeat."))),
+    };
+
+    Ok(Channel { input, filter, wrap })
+}
+
+fn parse_pass(name: PassName, pass: &Table) -> Result<Pass, ProjectError> {
+    let key = name.key();
+    let source = string(pass, key, "source")?.ok_or_else(|| ProjectError(format!("[{key}] needs a source.")))?;
+    let mut channels = [None; CHANNELS];
+
+    for (channel_key, value) in pass {
+        if channel_key == "source" {
+            continue;
+        }
+        let table_name = format!("{key}.{channel_key}");
+        let index = channel_key
+            .strip_prefix("channel")
+            .and_then(|i| i.parse::<usize>().ok())
+            .filter(|i| *i < CHANNELS);
+        match (index, value) {
+            (Some(index), value) => channels[index] = Some(parse_channel(table(value, &table_name)?, &table_name)?),
+            (None, Value::Table(_)) => {
+                return Err(ProjectError(format!("unknown table [{table_name}], expected channel0 to channel3.")))
+            }
+            (None, _) => {
+                return Err(ProjectError(format!(
+                    "unknown key {channel_key} in [{key}], expected source or channel0 to channel3."
+                )))
+            }
+        }
+    }
+
+    Ok(Pass { name, source: source.to_string(), channels })
+}
+
+impl Project {
+    pub fn parse(source: &str) -> Result<Self, ProjectError> {
+        let document = parse_toml(source).map_err(ProjectError)?;
+        let mut passes: Vec<Pass> = Vec::new();
+
+        for (pass_key, value) in &document {
+            let name = PassName::from_key(pass_key).ok_or_else(|| {
+                ProjectError(format!(
+                    "unknown pass [{pass_key}], expected buffer_a to buffer_d or image."
+                ))
+            })?;
+            passes.push(parse_pass(name, table(value, pass_key)?)?);
+        }
+
+        passes.sort_by_key(|pass| pass.name);
+
+        if passes.last().map(|pass| pass.name) != Some(PassName::Image) {
+            return Err(ProjectError("there's no [image] pass.".to_string()));
+        }
+        for pass in &passes {
+            for channel in pass.channels.iter().flatten() {
+                let Input::Buffer(input) = channel.input else {
+                    continue;
+                };
+                if !passes.iter().any(|p| p.name == input) {
+                    return Err(ProjectError(format!("[{}] reads {}, which has no pass.", pass.name.key(), input.key())));
+                }
+            }
+        }
+        if let [image] = passes.as_mut_slice() {
+            if image.channels.iter().all(Option::is_none) {
+                image.channels[0] = Some(Channel { input: Input::Audio, filter: Filter::default(), wrap: Wrap::default() });
+            }
+        }
+
+        Ok(Self { passes })
+    }
+
+    /// Reads the project file, the passes are compiled separately.
+    pub fn load(origin: &ShaderOrigin) -> Result<Self, ProjectError> {
+        let source = match origin {
+            ShaderOrigin::Embedded => Cow::Borrowed(EMBEDDED_PROJECT),
+            ShaderOrigin::Disk(root) => Cow::Owned(
+                fs::read_to_string(root.join(PROJECT_FILE)).map_err(|e| ProjectError(e.to_string()))?,
+            ),
+        };
+
+        Self::parse(&source)
+    }
+
+    /// The project file and every pass source, relative to the crate root.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = vec![PROJECT_FILE.to_string()];
+        files.extend(self.passes.iter().map(Pass::source_path));
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_project_compiles() {
+        let project = Project::load(&ShaderOrigin::Embedded).unwrap();
+        assert_eq!(project.passes.last().unwrap().name, PassName::Image);
+
+        for pass in &project.passes {
+            if let Err(e) = pass.compile(&ShaderOrigin::Embedded) {
+                panic!("{e}");
+            }
+        }
+    }
+
+    #[test]
+    fn parses_passes_and_channels() {
+        let source = r#"
+            [image]
+            source = "image.glsl"
+
+            [image.channel2]
+            input = "buffer_b"
+
+            [buffer_b]
+            source = "b.glsl"
+
+            [buffer_b.channel0]
+            input = "buffer_b"
+            filter = "nearest"
+            wrap = "repeat"
+        "#;
+        let project = Project::parse(source).unwrap();
+
+        let names: Vec<_> = project.passes.iter().map(|pass| pass.name).collect();
+        assert_eq!(names, [PassName::BufferB, PassName::Image]);
+        assert_eq!(
+            project.passes[0].channels[0],
+            Some(Channel { input: Input::Buffer(PassName::BufferB), filter: Filter::Nearest, wrap: Wrap::Repeat })
+        );
+        assert_eq!(
+            project.passes[1].channels[2],
+            Some(Channel { input: Input::Buffer(PassName::BufferB), filter: Filter::Linear, wrap: Wrap::Clamp })
+        );
+        assert_eq!(project.passes[1].channels[0], None);
+        assert_eq!(project.files()[1], "src/shader/shadertoy/b.glsl");
+
+        // a single pass reads the audio from iChannel0 unless it says otherwise
+        let audio = Some(Channel { input: Input::Audio, filter: Filter::Linear, wrap: Wrap::Clamp });
+        let project = Project::parse("[image]\nsource = 'i.glsl'").unwrap();
+        assert_eq!(project.passes[0].channels, [audio, None, None, None]);
+        let project = Project::parse("[image]\nsource = 'i.glsl'\n[image.channel1]\ninput = 'audio'").unwrap();
+        assert_eq!(project.passes[0].channels, [None, audio, None, None]);
+    }
+
+    #[test]
+    fn rejects_invalid_projects() {
+        let error = |source: &str| Project::parse(source).unwrap_err().0;
+
+        assert!(error("[buffer_a]\nsource = \"a.glsl\"\n").contains("no [image]"));
+        assert!(error("[image]\nsource = \"i.glsl\"\n[image.channel0]\ninput = \"buffer_c\"\n").contains("has no pass"));
+        assert!(error("[image]\nsource = \"i.glsl\"\n[image.channel4]\ninput = \"buffer_a\"\n").contains("channel0 to channel3"));
+        assert!(error("[image]\nsource = \"i.glsl\"\n[image.channel0]\ninput = \"image\"\n").contains("unknown input image"));
+        assert!(error("[image]\nsource = 1\n").contains("has to be a string"));
+        assert!(error("[image]\nsource = \"i.glsl\"\nchannel0 = \"buffer_a\"\n").contains("[image.channel0] has to be a table"));
+        assert!(error("[image]\nsource = \"i.glsl\"\nsorce = \"x\"\n").contains("unknown key sorce"));
+        assert!(error("[buffer_e]\nsource = \"e.glsl\"\n").contains("unknown pass"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Turns captured samples into shadertoy's audio texture, 512x2 texels with the spectrum
+//! in the first row and the waveform in the second.
+
+use std::f32::consts::PI;
+
+pub const FFT_SIZE: usize = 2048;
+pub const AUDIO_TEXTURE_WIDTH: usize = 512;
+pub const AUDIO_TEXTURE_HEIGHT: usize = 2;
+
+/// Lowest frequency in the first spectrum texel, the last one ends at half the sample rate.
+pub const MIN_FREQUENCY: f32 = 20.0;
+/// Decibels mapped to 0 and 1, the defaults of the web audio analyser shadertoy uses.
+pub const MIN_DB: f32 = -100.0;
+pub const MAX_DB: f32 = -30.0;
+/// Share of the last spectrum kept after 1/60 s.
+pub const SMOOTHING: f32 = 0.8;
+
+/// In-place radix-2 fft, the length has to be a power of two.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n, "fft needs two slices of the same power of two length.");
+
+    // bit reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Frequency at `t` in [0, 1] on a log scale from `MIN_FREQUENCY` to `max`.
+fn log_frequency(t: f32, max: f32) -> f32 {
+    MIN_FREQUENCY * (max / MIN_FREQUENCY).powf(t)
+}
+
+fn level(magnitude: f32) -> f32 {
+    let db = 20.0 * magnitude.max(1e-12).log10();
+    ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)
+}
+
+pub struct Analyzer {
+    // the last `FFT_SIZE` samples, newest last
+    history: Vec<f32>,
+    window: Vec<f32>,
+    window_sum: f32,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    // smoothed amplitude per texel
+    spectrum: [f32; AUDIO_TEXTURE_WIDTH],
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        // hann
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            history: vec![0.0; FFT_SIZE],
+            window_sum: window.iter().sum(),
+            window,
+            re: vec![0.0; FFT_SIZE],
+            im: vec![0.0; FFT_SIZE],
+            spectrum: [0.0; AUDIO_TEXTURE_WIDTH],
+        }
+    }
+
+    /// Appends mono samples, only the last `FFT_SIZE` are kept.
+    pub fn push(&mut self, samples: &[f32]) {
+        let samples = &samples[samples.len().saturating_sub(FFT_SIZE)..];
+        self.history.copy_within(samples.len().., 0);
+        self.history[FFT_SIZE - samples.len()..].copy_from_slice(samples);
+    }
+
+    /// Back to silence, for when the input goes away.
+    pub fn clear(&mut self) {
+        self.history.fill(0.0);
+        self.spectrum.fill(0.0);
+    }
+
+    /// Updates the spectrum from the current samples, `dt` is the time since the last call.
+    pub fn analyze(&mut self, sample_rate: u32, dt: f32) {
+        for (i, sample) in self.history.iter().enumerate() {
+            self.re[i] = sample * self.window[i];
+            self.im[i] = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        // a full scale sine ends up at 1
+        let scale = 2.0 / self.window_sum;
+        let magnitudes = |k: usize| (self.re[k].hypot(self.im[k])) * scale;
+        let nyquist = sample_rate as f32 / 2.0;
+        let bin_width = sample_rate as f32 / FFT_SIZE as f32;
+        let smoothing = SMOOTHING.powf(dt * 60.0);
+
+        for (i, smoothed) in self.spectrum.iter_mut().enumerate() {
+            let low = log_frequency(i as f32 / AUDIO_TEXTURE_WIDTH as f32, nyquist) / bin_width;
+            let high = log_frequency((i + 1) as f32 / AUDIO_TEXTURE_WIDTH as f32, nyquist) / bin_width;
+            let first = low.ceil() as usize;
+            let last = (high.floor() as usize).min(FFT_SIZE / 2);
+
+            let magnitude = if first <= last {
+                (first..=last).map(magnitudes).fold(0.0, f32::max)
+            } else {
+                // narrower than a bin at the low end, interpolate at the center
+                let center = (low + high) / 2.0;
+                let k = (center.floor() as usize).min(FFT_SIZE / 2 - 1);
+                let t = center - k as f32;
+                magnitudes(k) * (1.0 - t) + magnitudes(k + 1) * t
+            };
+            *smoothed = smoothing * *smoothed + (1.0 - smoothing) * magnitude;
+        }
+    }
+
+    /// Spectrum levels in [0, 1] from `MIN_FREQUENCY` to half the sample rate on a log scale.
+    pub fn spectrum(&self) -> [f32; AUDIO_TEXTURE_WIDTH] {
+        self.spectrum.map(level)
+    }
+
+    /// The newest samples moved to [0, 1], silence is 0.5.
+    pub fn waveform(&self) -> [f32; AUDIO_TEXTURE_WIDTH] {
+        let newest = &self.history[FFT_SIZE - AUDIO_TEXTURE_WIDTH..];
+        std::array::from_fn(|i| (0.5 + 0.5 * newest[i]).clamp(0.0, 1.0))
+    }
+
+    /// Both rows of the `R8Unorm` audio texture.
+    pub fn texels(&self) -> Vec<u8> {
+        self.spectrum()
+            .iter()
+            .chain(self.waveform().iter())
+            .map(|v| (v * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    fn sine(frequency: f32, amplitude: f32, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    fn loudest(spectrum: &[f32]) -> usize {
+        (0..spectrum.len()).max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b])).unwrap()
+    }
+
+    // the texel whose frequency range holds `frequency`
+    fn texel(frequency: f32) -> usize {
+        let t = (frequency / MIN_FREQUENCY).ln() / (RATE as f32 / 2.0 / MIN_FREQUENCY).ln();
+        (t * AUDIO_TEXTURE_WIDTH as f32) as usize
+    }
+
+    #[test]
+    fn fft_matches_dft() {
+        let signal: Vec<f32> = (0..16).map(|i| ((i * 7) % 5) as f32 - 2.0).collect();
+        let (mut re, mut im) = (signal.clone(), vec![0.0; 16]);
+        fft(&mut re, &mut im);
+
+        for k in 0..16 {
+            let (mut dft_re, mut dft_im) = (0.0, 0.0);
+            for (n, x) in signal.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f32 / 16.0;
+                dft_re += x * angle.cos();
+                dft_im += x * angle.sin();
+            }
+            assert!((re[k] - dft_re).abs() < 1e-4 && (im[k] - dft_im).abs() < 1e-4, "bin {k}");
+        }
+    }
+
+    #[test]
+    fn sines_peak_at_their_frequency() {
+        for frequency in [110.0, 1_000.0, 6_000.0] {
+            let mut analyzer = Analyzer::new();
+            analyzer.push(&sine(frequency, 0.5, FFT_SIZE));
+            analyzer.analyze(RATE, 1.0);
+
+            // levels saturate around loud peaks, the amplitudes don't
+            let peak = loudest(&analyzer.spectrum);
+            // texels at the low end are narrower than a bin, the peak can be off by up to one
+            let bin_width = RATE as f32 / FFT_SIZE as f32;
+            let texel_frequency = |i: usize| log_frequency(i as f32 / AUDIO_TEXTURE_WIDTH as f32, RATE as f32 / 2.0);
+            assert!(texel_frequency(peak) - bin_width <= frequency, "{frequency} Hz");
+            assert!(texel_frequency(peak + 1) + bin_width >= frequency, "{frequency} Hz");
+            assert!((analyzer.spectrum[peak] - 0.5).abs() < 0.1, "{frequency} Hz");
+            let spectrum = analyzer.spectrum();
+            assert_eq!(spectrum[peak], 1.0);
+            // an octave away only the window's side lobes are left
+            assert!(analyzer.spectrum[texel(frequency * 2.0)] < 0.01 * analyzer.spectrum[peak], "{frequency} Hz");
+        }
+    }
+
+    #[test]
+    fn quiet_sines_stay_below_full_level() {
+        let mut analyzer = Analyzer::new();
+        // -60 db, halfway between MIN_DB and MAX_DB is -65
+        analyzer.push(&sine(1_000.0, 0.001, FFT_SIZE));
+        analyzer.analyze(RATE, 1.0);
+
+        let peak = analyzer.spectrum()[texel(1_000.0)];
+        assert!((peak - 40.0 / 70.0).abs() < 0.05, "{peak}");
+    }
+
+    #[test]
+    fn spectrum_is_smoothed() {
+        let mut analyzer = Analyzer::new();
+        analyzer.push(&sine(1_000.0, 0.5, FFT_SIZE));
+        analyzer.analyze(RATE, 1.0);
+        let loud = analyzer.spectrum[texel(1_000.0)];
+
+        // one frame of silence only takes away a fifth
+        analyzer.push(&[0.0; FFT_SIZE]);
+        analyzer.analyze(RATE, 1.0 / 60.0);
+        let after = analyzer.spectrum[texel(1_000.0)];
+        assert!((after / loud - SMOOTHING).abs() < 1e-4);
+    }
+
+    #[test]
+    fn silence_fills_texture_rows() {
+        let mut analyzer = Analyzer::new();
+        analyzer.push(&[0.0; 100]);
+        analyzer.analyze(RATE, 1.0);
+        let texels = analyzer.texels();
+
+        assert_eq!(texels.len(), AUDIO_TEXTURE_WIDTH * AUDIO_TEXTURE_HEIGHT);
+        assert!(texels[..AUDIO_TEXTURE_WIDTH].iter().all(|&t| t == 0));
+        assert!(texels[AUDIO_TEXTURE_WIDTH..].iter().all(|&t| t == 128));
+
+        analyzer.push(&[1.0, -1.0]);
+        assert_eq!(analyzer.texels()[1022..], [255, 0]);
+    }
+}
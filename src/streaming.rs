@@ -1,10 +1,12 @@
+use crate::spectrum::{AUDIO_TEXTURE_HEIGHT, AUDIO_TEXTURE_WIDTH};
 use crate::uniform::Uniform;
-use crate::vertex::Vertex;
+use crate::vertex::{Instance, Vertex};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, Device,
-    ShaderStages,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    Device, Sampler, SamplerBindingType, ShaderStages, Texture, TextureSampleType, TextureView,
+    TextureViewDimension,
 };
 use winit::dpi::PhysicalSize;
 
@@ -21,6 +23,55 @@ pub fn create_uniform_buffer(dev: &Device, size: PhysicalSize<u32>) -> (Uniform,
     (uniform, uniform_buffer)
 }
 
+pub fn create_svo_buffer(dev: &Device, svo: &[u32]) -> Buffer {
+    dev.create_buffer_init(&BufferInitDescriptor {
+        label: Some("svo_buffer"),
+        contents: bytemuck::cast_slice(svo),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    })
+}
+
+/// The 512x2 audio texture, black until the first `write_audio_texture`.
+pub fn create_audio_texture(dev: &Device) -> (Texture, TextureView, Sampler) {
+    let texture = dev.create_texture(&wgpu::TextureDescriptor {
+        label: Some("audio_texture"),
+        size: wgpu::Extent3d {
+            width: AUDIO_TEXTURE_WIDTH as u32,
+            height: AUDIO_TEXTURE_HEIGHT as u32,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = dev.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("audio_sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    (texture, view, sampler)
+}
+
+/// Uploads both rows from `Analyzer::texels`.
+pub fn write_audio_texture(queue: &wgpu::Queue, texture: &Texture, texels: &[u8]) {
+    queue.write_texture(
+        texture.as_image_copy(),
+        texels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(AUDIO_TEXTURE_WIDTH as u32),
+            rows_per_image: Some(AUDIO_TEXTURE_HEIGHT as u32),
+        },
+        texture.size(),
+    );
+}
+
 pub fn create_polygon_buffers(
     dev: &Device,
     vertices: &Vec<Vertex>,
@@ -44,13 +95,24 @@ pub fn create_polygon_buffers(
     (vertex_buffer, index_buffer, num_indices)
 }
 
-/// Creates bind group layout and bind group for uniform and SVO buffer .
+pub fn create_instance_buffer(dev: &Device, instances: &[Instance]) -> (Buffer, u32) {
+    let instance_buffer = dev.create_buffer_init(&BufferInitDescriptor {
+        label: Some("instance_buffer"),
+        contents: bytemuck::cast_slice(instances),
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+    });
+
+    (instance_buffer, instances.len() as u32)
+}
+
+/// Creates bind group layout and bind group for uniform, SVO buffer and audio texture.
 ///
 /// # Arguments
 ///
 /// * `dev` - A reference to the wgpu device.
 /// * `uniform_buffer` - A reference to the uniform buffer.
 /// * `svo_buffer` - A reference to the SVO buffer.
+/// * `audio_view` - The audio texture from `create_audio_texture` and its sampler.
 ///
 /// # Returns
 ///
@@ -58,27 +120,71 @@ pub fn create_polygon_buffers(
 pub fn create_buffer_descriptors(
     dev: &Device,
     uniform_buffer: &Buffer,
+    svo_buffer: &Buffer,
+    (audio_view, audio_sampler): (&TextureView, &Sampler),
 ) -> (BindGroupLayout, BindGroup) {
     let bind_group_layout = dev.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        entries: &[BindGroupLayoutEntry {
-            binding: 0,
-            visibility: ShaderStages::all(),
-            ty: BindingType::Buffer {
-                ty: BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::all(),
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
             },
-            count: None,
-        }],
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
         label: Some("bind_group_layout"),
     });
 
     let bind_group = dev.create_bind_group(&BindGroupDescriptor {
         layout: &bind_group_layout,
-        entries: &[BindGroupEntry {
-            binding: 0,
-            resource: uniform_buffer.as_entire_binding(),
-        }],
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: svo_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(audio_view),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Sampler(audio_sampler),
+            },
+        ],
         label: Some("bind_group"),
     });
 
@@ -0,0 +1,716 @@
+use glam::UVec3;
+use std::collections::VecDeque;
+use std::fmt;
+
+// must match the constants in shader.wgsl
+pub const CHILD_OFFSET: u32 = 24;
+pub const CHILD_PTR_MASK: u32 = (1 << CHILD_OFFSET) - 1;
+pub const STACK_SIZE: u32 = 23;
+
+/// deepest tree the shader traversal stack can handle
+pub const MAX_DEPTH: u32 = STACK_SIZE - 1;
+/// materials share the low 24 bits with child pointers, 0 means empty
+pub const MAX_MATERIAL: u32 = CHILD_PTR_MASK;
+/// start of an `.svo` file, see `Octree::to_file`
+pub const FILE_MAGIC: &[u8; 4] = b"PSVO";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SvoError {
+    DepthOutOfRange(u32),
+    PositionOutOfBounds(UVec3),
+    InvalidMaterial(u32),
+    DenseSizeMismatch { expected: usize, found: usize },
+    EmptyBranch { depth: u32 },
+    CollapsibleBranch { depth: u32 },
+    LeafBelowMaxDepth { depth: u32 },
+    PointerOverflow(usize),
+    PointerOutOfBounds { index: usize, ptr: u32 },
+    UnusedSlot { index: usize, value: u32 },
+    TrailingData { expected: usize, found: usize },
+    NotAnSvoFile,
+}
+
+impl fmt::Display for SvoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvoError::DepthOutOfRange(depth) => {
+                write!(f, "octree depth {depth} is out of range (1..={MAX_DEPTH}).")
+            }
+            SvoError::PositionOutOfBounds(pos) => {
+                write!(f, "voxel position {pos} lies outside the octree.")
+            }
+            SvoError::InvalidMaterial(mat) => {
+                write!(f, "material {mat} is invalid (1..={MAX_MATERIAL}).")
+            }
+            SvoError::DenseSizeMismatch { expected, found } => {
+                write!(f, "dense grid has {found} voxels, expected {expected}.")
+            }
+            SvoError::EmptyBranch { depth } => {
+                write!(f, "branch at depth {depth} has no children.")
+            }
+            SvoError::CollapsibleBranch { depth } => {
+                write!(f, "branch at depth {depth} only holds equal leaves.")
+            }
+            SvoError::LeafBelowMaxDepth { depth } => {
+                write!(f, "branch at depth {depth} exceeds the octree depth.")
+            }
+            SvoError::PointerOverflow(len) => {
+                write!(f, "{len} nodes do not fit into 24 bit child pointers.")
+            }
+            SvoError::PointerOutOfBounds { index, ptr } => {
+                write!(f, "node {index} points to {ptr}, which is out of bounds.")
+            }
+            SvoError::UnusedSlot { index, value } => {
+                write!(
+                    f,
+                    "slot {index} is not marked valid but holds {value:#010x}."
+                )
+            }
+            SvoError::TrailingData { expected, found } => {
+                write!(
+                    f,
+                    "buffer has {found} words, but the tree only uses {expected}."
+                )
+            }
+            SvoError::NotAnSvoFile => {
+                write!(f, "not an svo file, `patibu convert` makes one from a .vox model.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SvoError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Node {
+    #[default]
+    Empty,
+    Leaf(u32),
+    Branch(Box<[Node; 8]>),
+}
+
+impl Node {
+    /// Builds a branch, collapsing it if all children are empty or the same leaf.
+    pub fn branch(children: [Node; 8]) -> Node {
+        if Self::is_collapsible(&children) {
+            return children[0].clone();
+        }
+
+        Node::Branch(Box::new(children))
+    }
+
+    fn is_collapsible(children: &[Node; 8]) -> bool {
+        matches!(children[0], Node::Empty | Node::Leaf(_))
+            && children.iter().all(|c| *c == children[0])
+    }
+
+    fn valid_mask(children: &[Node; 8]) -> u32 {
+        children
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != Node::Empty)
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+}
+
+/// Octant index of a child, bit 0 = upper x half, bit 1 = upper y, bit 2 = upper z.
+pub fn octant(pos: UVec3, level_shift: u32) -> usize {
+    (((pos.x >> level_shift) & 1)
+        | (((pos.y >> level_shift) & 1) << 1)
+        | (((pos.z >> level_shift) & 1) << 2)) as usize
+}
+
+/// Sparse voxel octree spanning `2^depth` voxels per axis.
+///
+/// In the shader the octree occupies the cube `[1, 2]^3`, voxel `(x, y, z)` covers
+/// `1 + (x, y, z) / 2^depth` to `1 + (x + 1, y + 1, z + 1) / 2^depth`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Octree {
+    depth: u32,
+    root: Node,
+}
+
+impl Octree {
+    pub fn new(depth: u32) -> Result<Self, SvoError> {
+        if depth == 0 || depth > MAX_DEPTH {
+            return Err(SvoError::DepthOutOfRange(depth));
+        }
+
+        Ok(Self {
+            depth,
+            root: Node::Empty,
+        })
+    }
+
+    /// Builds an octree from a dense grid indexed by `x + y * side + z * side * side`,
+    /// where 0 marks an empty voxel.
+    pub fn from_dense(depth: u32, voxels: &[u32]) -> Result<Self, SvoError> {
+        let mut octree = Self::new(depth)?;
+        let side = octree.side() as usize;
+
+        let expected = side * side * side;
+        if voxels.len() != expected {
+            return Err(SvoError::DenseSizeMismatch {
+                expected,
+                found: voxels.len(),
+            });
+        }
+
+        if let Some(&mat) = voxels.iter().find(|&&mat| mat > MAX_MATERIAL) {
+            return Err(SvoError::InvalidMaterial(mat));
+        }
+
+        octree.root = Self::build_dense(voxels, side, UVec3::ZERO, depth);
+        Ok(octree)
+    }
+
+    fn build_dense(voxels: &[u32], side: usize, origin: UVec3, level: u32) -> Node {
+        if level == 0 {
+            let i = origin.x as usize + origin.y as usize * side + origin.z as usize * side * side;
+            return match voxels[i] {
+                0 => Node::Empty,
+                mat => Node::Leaf(mat),
+            };
+        }
+
+        let half = 1 << (level - 1);
+        let children = std::array::from_fn(|i| {
+            let offset = UVec3::new(i as u32 & 1, (i as u32 >> 1) & 1, (i as u32 >> 2) & 1);
+            Self::build_dense(voxels, side, origin + offset * half, level - 1)
+        });
+
+        Node::branch(children)
+    }
+
+    /// Builds an octree from `(position, material)` pairs, later voxels overwrite earlier ones.
+    pub fn from_voxels<I>(depth: u32, voxels: I) -> Result<Self, SvoError>
+    where
+        I: IntoIterator<Item = (UVec3, u32)>,
+    {
+        let mut octree = Self::new(depth)?;
+        for (pos, mat) in voxels {
+            octree.insert(pos, mat)?;
+        }
+
+        Ok(octree)
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn side(&self) -> u32 {
+        1 << self.depth
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Sets a single voxel, a material of 0 clears it.
+    pub fn insert(&mut self, pos: UVec3, material: u32) -> Result<(), SvoError> {
+        if pos.cmpge(UVec3::splat(self.side())).any() {
+            return Err(SvoError::PositionOutOfBounds(pos));
+        }
+        if material > MAX_MATERIAL {
+            return Err(SvoError::InvalidMaterial(material));
+        }
+
+        let new = match material {
+            0 => Node::Empty,
+            mat => Node::Leaf(mat),
+        };
+        Self::insert_node(&mut self.root, pos, self.depth, new);
+
+        Ok(())
+    }
+
+    fn insert_node(node: &mut Node, pos: UVec3, level: u32, new: Node) {
+        if level == 0 {
+            *node = new;
+            return;
+        }
+
+        if *node == new {
+            return;
+        }
+
+        let mut children = match std::mem::replace(node, Node::Empty) {
+            Node::Branch(children) => children,
+            other => Box::new(std::array::from_fn(|_| other.clone())),
+        };
+
+        Self::insert_node(&mut children[octant(pos, level - 1)], pos, level - 1, new);
+        *node = Node::branch(*children);
+    }
+
+    /// Material at `pos`, 0 if empty or out of bounds.
+    pub fn get(&self, pos: UVec3) -> u32 {
+        if pos.cmpge(UVec3::splat(self.side())).any() {
+            return 0;
+        }
+
+        let mut node = &self.root;
+        let mut level = self.depth;
+        loop {
+            match node {
+                Node::Empty => return 0,
+                Node::Leaf(mat) => return *mat,
+                Node::Branch(children) => {
+                    level -= 1;
+                    node = &children[octant(pos, level)];
+                }
+            }
+        }
+    }
+
+    /// Checks the structural invariants `serialize` relies on.
+    pub fn validate(&self) -> Result<(), SvoError> {
+        if self.depth == 0 || self.depth > MAX_DEPTH {
+            return Err(SvoError::DepthOutOfRange(self.depth));
+        }
+
+        Self::validate_node(&self.root, 0, self.depth)
+    }
+
+    fn validate_node(node: &Node, depth: u32, max_depth: u32) -> Result<(), SvoError> {
+        match node {
+            Node::Empty => Ok(()),
+            Node::Leaf(mat) => match *mat {
+                1..=MAX_MATERIAL => Ok(()),
+                mat => Err(SvoError::InvalidMaterial(mat)),
+            },
+            Node::Branch(children) => {
+                if depth >= max_depth {
+                    return Err(SvoError::LeafBelowMaxDepth { depth });
+                }
+                if Node::valid_mask(children) == 0 {
+                    return Err(SvoError::EmptyBranch { depth });
+                }
+                if Node::is_collapsible(children) {
+                    return Err(SvoError::CollapsibleBranch { depth });
+                }
+
+                children
+                    .iter()
+                    .try_for_each(|c| Self::validate_node(c, depth + 1, max_depth))
+            }
+        }
+    }
+
+    /// Serializes the octree into the `svo` storage buffer layout read by `raymarch_leaf`.
+    ///
+    /// Every word is either a branch descriptor, a leaf or an empty slot:
+    ///
+    /// * branch - bits 24..32 hold the valid mask (bit `24 + octant` set for every non empty child),
+    ///   bits 0..24 the absolute index of the first of eight consecutive child slots.
+    /// * leaf - top byte 0, material in bits 0..24.
+    /// * empty - 0, only found in child slots whose valid bit is cleared.
+    ///
+    /// The root descriptor is stored at index 0 and is always a branch, as the shader only
+    /// checks children for leaves. An empty octree serializes to a single 0 (no valid children),
+    /// a solid one to a root with eight equal leaves. Children are laid out breadth first.
+    pub fn serialize(&self) -> Result<Vec<u32>, SvoError> {
+        self.validate()?;
+
+        let solid_root;
+        let root = match &self.root {
+            Node::Empty => return Ok(vec![0]),
+            Node::Leaf(mat) => {
+                solid_root = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(*mat))));
+                &solid_root
+            }
+            branch => branch,
+        };
+
+        let mut data = vec![0];
+        let mut queue = VecDeque::from([(0usize, root)]);
+
+        while let Some((index, node)) = queue.pop_front() {
+            data[index] = match node {
+                Node::Empty => 0,
+                Node::Leaf(mat) => *mat,
+                Node::Branch(children) => {
+                    let ptr = data.len();
+                    if ptr + 8 > CHILD_PTR_MASK as usize + 1 {
+                        return Err(SvoError::PointerOverflow(ptr + 8));
+                    }
+
+                    data.resize(ptr + 8, 0);
+                    queue.extend(children.iter().enumerate().map(|(i, c)| (ptr + i, c)));
+
+                    (Node::valid_mask(children) << CHILD_OFFSET) | ptr as u32
+                }
+            };
+        }
+
+        Ok(data)
+    }
+
+    /// Decodes a buffer produced by `serialize`, rejecting anything the shader would misread.
+    pub fn deserialize(depth: u32, data: &[u32]) -> Result<Self, SvoError> {
+        let mut octree = Self::new(depth)?;
+        let Some(&root) = data.first() else {
+            return Err(SvoError::PointerOutOfBounds { index: 0, ptr: 0 });
+        };
+
+        let mut used = 1;
+        octree.root = match root {
+            0 => Node::Empty,
+            root if root >> CHILD_OFFSET == 0 => {
+                return Err(SvoError::EmptyBranch { depth: 0 });
+            }
+            root => match Self::decode_node(data, 0, root, 0, depth, &mut used)? {
+                Node::Branch(children) if Node::is_collapsible(&children) => children[0].clone(),
+                node => node,
+            },
+        };
+
+        if used != data.len() {
+            return Err(SvoError::TrailingData {
+                expected: used,
+                found: data.len(),
+            });
+        }
+
+        octree.validate()?;
+        Ok(octree)
+    }
+
+    fn decode_node(
+        data: &[u32],
+        index: usize,
+        word: u32,
+        depth: u32,
+        max_depth: u32,
+        used: &mut usize,
+    ) -> Result<Node, SvoError> {
+        let valid_mask = word >> CHILD_OFFSET;
+        if valid_mask == 0 {
+            return Ok(match word {
+                0 => Node::Empty,
+                mat => Node::Leaf(mat),
+            });
+        }
+
+        if depth >= max_depth {
+            return Err(SvoError::LeafBelowMaxDepth { depth });
+        }
+
+        let ptr = (word & CHILD_PTR_MASK) as usize;
+        if ptr <= index || ptr + 8 > data.len() {
+            return Err(SvoError::PointerOutOfBounds {
+                index,
+                ptr: ptr as u32,
+            });
+        }
+        *used += 8;
+
+        let mut children: [Node; 8] = Default::default();
+        for (i, child) in children.iter_mut().enumerate() {
+            let slot = data[ptr + i];
+            if valid_mask & (1 << i) == 0 {
+                if slot != 0 {
+                    return Err(SvoError::UnusedSlot {
+                        index: ptr + i,
+                        value: slot,
+                    });
+                }
+                continue;
+            }
+
+            *child = Self::decode_node(data, ptr + i, slot, depth + 1, max_depth, used)?;
+            if *child == Node::Empty {
+                return Err(SvoError::EmptyBranch { depth: depth + 1 });
+            }
+        }
+
+        Ok(Node::Branch(Box::new(children)))
+    }
+}
+
+impl Octree {
+    /// Contents of an `.svo` file, `FILE_MAGIC`, the depth and the `serialize`d words,
+    /// all little endian.
+    pub fn to_file(&self) -> Result<Vec<u8>, SvoError> {
+        let data = self.serialize()?;
+
+        let mut bytes = Vec::with_capacity(8 + data.len() * 4);
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&self.depth.to_le_bytes());
+        bytes.extend(data.iter().flat_map(|word| word.to_le_bytes()));
+
+        Ok(bytes)
+    }
+
+    pub fn from_file(bytes: &[u8]) -> Result<Self, SvoError> {
+        let (header, words) = bytes.split_at_checked(8).ok_or(SvoError::NotAnSvoFile)?;
+        if &header[..4] != FILE_MAGIC || words.len() % 4 != 0 {
+            return Err(SvoError::NotAnSvoFile);
+        }
+
+        let depth = u32::from_le_bytes(header[4..].try_into().expect("the header has 8 bytes."));
+        let data: Vec<u32> = words
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().expect("chunks have 4 bytes.")))
+            .collect();
+
+        Self::deserialize(depth, &data)
+    }
+}
+
+/// Sphere resting on a checkered floor, used when no model is loaded.
+pub fn demo_scene(depth: u32) -> Result<Octree, SvoError> {
+    let side = 1u32 << depth.min(MAX_DEPTH);
+    let center = side as f32 * 0.5;
+    let radius = side as f32 * 0.3;
+    let floor = (side / 8).max(1);
+
+    let voxels = (0..side * side * side)
+        .map(|i| {
+            let pos = UVec3::new(i % side, (i / side) % side, i / (side * side));
+            let offset = pos.as_vec3() + 0.5 - glam::Vec3::new(center, center, center);
+
+            if offset.length() <= radius {
+                1
+            } else if pos.y < floor {
+                2 + (pos.x / floor + pos.z / floor) % 2
+            } else {
+                0
+            }
+        })
+        .collect::<Vec<u32>>();
+
+    Octree::from_dense(depth, &voxels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dense_grid(depth: u32, f: impl Fn(UVec3) -> u32) -> Vec<u32> {
+        let side = 1u32 << depth;
+        (0..side * side * side)
+            .map(|i| f(UVec3::new(i % side, (i / side) % side, i / (side * side))))
+            .collect()
+    }
+
+    /// Walks the raw buffer like the shader does and checks every word.
+    fn check_buffer(octree: &Octree, data: &[u32]) {
+        let mut visited = vec![false; data.len()];
+        visited[0] = true;
+
+        fn walk(
+            octree: &Octree,
+            data: &[u32],
+            visited: &mut [bool],
+            index: usize,
+            origin: UVec3,
+            level: u32,
+        ) {
+            let word = data[index];
+            let valid_mask = word >> CHILD_OFFSET;
+
+            if valid_mask == 0 {
+                // leaf or empty, the whole cube must hold this material
+                let size = 1 << level;
+                for z in 0..size {
+                    for y in 0..size {
+                        for x in 0..size {
+                            let pos = origin + UVec3::new(x, y, z);
+                            assert_eq!(octree.get(pos), word, "voxel {pos} of node {index}");
+                        }
+                    }
+                }
+                return;
+            }
+
+            assert!(level > 0, "branch {index} below the last level");
+            let ptr = (word & CHILD_PTR_MASK) as usize;
+            assert!(ptr + 8 <= data.len(), "node {index} points out of bounds");
+
+            let half = 1 << (level - 1);
+            for i in 0..8 {
+                assert!(!visited[ptr + i], "slot {} referenced twice", ptr + i);
+                visited[ptr + i] = true;
+
+                let offset = UVec3::new(i as u32 & 1, (i as u32 >> 1) & 1, (i as u32 >> 2) & 1);
+                let child_origin = origin + offset * half;
+                if valid_mask & (1 << i) != 0 {
+                    assert_ne!(data[ptr + i], 0, "valid slot {} is empty", ptr + i);
+                    walk(octree, data, visited, ptr + i, child_origin, level - 1);
+                } else {
+                    assert_eq!(data[ptr + i], 0, "invalid slot {} is not empty", ptr + i);
+                    assert_eq!(octree.get(child_origin), 0);
+                }
+            }
+        }
+
+        walk(octree, data, &mut visited, 0, UVec3::ZERO, octree.depth());
+        assert!(
+            visited.iter().all(|&v| v),
+            "buffer contains unreferenced words"
+        );
+    }
+
+    fn round_trip(octree: &Octree) -> Vec<u32> {
+        let data = octree.serialize().unwrap();
+        check_buffer(octree, &data);
+        assert_eq!(Octree::deserialize(octree.depth(), &data).unwrap(), *octree);
+        data
+    }
+
+    #[test]
+    fn empty_octree() {
+        let octree = Octree::new(3).unwrap();
+        assert_eq!(round_trip(&octree), vec![0]);
+    }
+
+    #[test]
+    fn single_voxel() {
+        let octree = Octree::from_voxels(1, [(UVec3::new(1, 0, 1), 7)]).unwrap();
+        let data = round_trip(&octree);
+
+        assert_eq!(data.len(), 9);
+        assert_eq!(data[0], (1 << (CHILD_OFFSET + 5)) | 1);
+        assert_eq!(data[1 + 5], 7);
+        assert_eq!(data.iter().filter(|&&w| w != 0).count(), 2);
+    }
+
+    #[test]
+    fn full_grid_collapses_to_leaf() {
+        let octree = Octree::from_dense(3, &dense_grid(3, |_| 2)).unwrap();
+        assert_eq!(*octree.root(), Node::Leaf(2));
+
+        // the root stays a branch, so the shader never reads it as a leaf
+        let data = round_trip(&octree);
+        assert_eq!(data, [(0xFF << CHILD_OFFSET) | 1, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn dense_matches_voxels() {
+        let depth = 4;
+        let shape = |p: UVec3| {
+            let c = p.as_ivec3() * 2 - 15;
+            match c.length_squared() {
+                0..=100 => 1 + (p.x + p.y) % 3,
+                _ => 0,
+            }
+        };
+
+        let dense = Octree::from_dense(depth, &dense_grid(depth, shape)).unwrap();
+        let sparse = Octree::from_voxels(
+            depth,
+            dense_grid(depth, |p| p.x | (p.y << 8) | (p.z << 16))
+                .into_iter()
+                .map(|w| UVec3::new(w & 0xFF, (w >> 8) & 0xFF, w >> 16))
+                .map(|p| (p, shape(p)))
+                .filter(|&(_, mat)| mat != 0),
+        )
+        .unwrap();
+
+        assert_eq!(dense, sparse);
+        round_trip(&dense);
+    }
+
+    #[test]
+    fn insert_and_clear() {
+        let mut octree = Octree::new(2).unwrap();
+        octree.insert(UVec3::new(3, 3, 3), 5).unwrap();
+        octree.insert(UVec3::new(0, 1, 2), 6).unwrap();
+        assert_eq!(octree.get(UVec3::new(3, 3, 3)), 5);
+        assert_eq!(octree.get(UVec3::new(0, 1, 2)), 6);
+        round_trip(&octree);
+
+        octree.insert(UVec3::new(3, 3, 3), 0).unwrap();
+        octree.insert(UVec3::new(0, 1, 2), 0).unwrap();
+        assert_eq!(*octree.root(), Node::Empty);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(Octree::new(0), Err(SvoError::DepthOutOfRange(0)));
+        assert_eq!(
+            Octree::new(MAX_DEPTH + 1),
+            Err(SvoError::DepthOutOfRange(MAX_DEPTH + 1))
+        );
+
+        let mut octree = Octree::new(2).unwrap();
+        assert_eq!(
+            octree.insert(UVec3::new(4, 0, 0), 1),
+            Err(SvoError::PositionOutOfBounds(UVec3::new(4, 0, 0)))
+        );
+        assert_eq!(
+            octree.insert(UVec3::ZERO, MAX_MATERIAL + 1),
+            Err(SvoError::InvalidMaterial(MAX_MATERIAL + 1))
+        );
+        assert_eq!(
+            Octree::from_dense(2, &[0; 8]),
+            Err(SvoError::DenseSizeMismatch {
+                expected: 64,
+                found: 8
+            })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_malformed_trees() {
+        let mut octree = Octree::new(1).unwrap();
+        octree.root = Node::Branch(Box::default());
+        assert_eq!(octree.validate(), Err(SvoError::EmptyBranch { depth: 0 }));
+
+        octree.root = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(1))));
+        assert_eq!(
+            octree.validate(),
+            Err(SvoError::CollapsibleBranch { depth: 0 })
+        );
+
+        let mut children: [Node; 8] = Default::default();
+        children[0] = Node::Branch(Box::new(std::array::from_fn(|i| Node::Leaf(i as u32 + 1))));
+        octree.root = Node::Branch(Box::new(children));
+        assert_eq!(
+            octree.validate(),
+            Err(SvoError::LeafBelowMaxDepth { depth: 1 })
+        );
+    }
+
+    #[test]
+    fn deserialize_rejects_corrupt_buffers() {
+        let octree = Octree::from_voxels(2, [(UVec3::new(1, 2, 3), 4), (UVec3::ZERO, 1)]).unwrap();
+        let data = octree.serialize().unwrap();
+
+        let mut bad_ptr = data.clone();
+        bad_ptr[0] = (bad_ptr[0] & !CHILD_PTR_MASK) | data.len() as u32;
+        assert!(matches!(
+            Octree::deserialize(2, &bad_ptr),
+            Err(SvoError::PointerOutOfBounds { index: 0, .. })
+        ));
+
+        let mut stray = data.clone();
+        let unused = (1..9).find(|&i| stray[i] == 0).unwrap();
+        stray[unused] = 9;
+        assert!(matches!(
+            Octree::deserialize(2, &stray),
+            Err(SvoError::UnusedSlot { .. })
+        ));
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Octree::deserialize(2, &trailing),
+            Err(SvoError::TrailingData { .. })
+        ));
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let octree = demo_scene(3).unwrap();
+        let bytes = octree.to_file().unwrap();
+        assert_eq!(&bytes[..4], FILE_MAGIC);
+        assert_eq!(Octree::from_file(&bytes), Ok(octree));
+
+        assert_eq!(Octree::from_file(b"VOX \x96\0\0\0"), Err(SvoError::NotAnSvoFile));
+        assert_eq!(Octree::from_file(&bytes[..bytes.len() - 1]), Err(SvoError::NotAnSvoFile));
+        assert_eq!(Octree::from_file(&bytes[..6]), Err(SvoError::NotAnSvoFile));
+    }
+}
@@ -1,30 +1,329 @@
+use crate::camera::{FlyCamera, RayBasis};
 use glam::{Mat4, UVec2, Vec2};
+use std::fmt;
+use std::mem::{offset_of, size_of};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniform {
+    // projection times view of the active camera, meshes add their model matrix per instance
     pub proj: [[f32; 4]; 4],
     pub res: [u32; 2],
     pub mouse: [f32; 2],
+    // milliseconds, wall clock in the window and fixed steps when exporting
     pub time: u32,
     pub _padding: [u32; 3],
+    // ray basis for the octree raymarcher, w is unused
+    pub cam_pos: [f32; 4],
+    pub cam_dir: [f32; 4],
+    pub cam_plane_u: [f32; 4],
+    pub cam_plane_v: [f32; 4],
+    // shadertoy's iMouse and iDate, see `ShadertoyInputs`
+    pub mouse_click: [f32; 4],
+    pub date: [f32; 4],
+    // seconds
+    pub time_delta: f32,
+    pub frame: u32,
+    // audio input, see `BeatDetector`
+    pub beat: f32,
+    pub bpm: f32,
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+    pub _padding_end: [u32; 1],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    U32,
+    F32,
+    Vec2U32,
+    Vec2F32,
+    Vec4F32,
+    Mat4F32,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldType::U32 => "u32",
+            FieldType::F32 => "f32",
+            FieldType::Vec2U32 => "vec2<u32>",
+            FieldType::Vec2F32 => "vec2<f32>",
+            FieldType::Vec4F32 => "vec4<f32>",
+            FieldType::Mat4F32 => "mat4x4<f32>",
+        })
+    }
+}
+
+impl FieldType {
+    fn matches(&self, inner: &naga::TypeInner) -> bool {
+        use naga::{ScalarKind, TypeInner, VectorSize};
+
+        match (self, inner) {
+            (FieldType::U32, TypeInner::Scalar(s)) => s.kind == ScalarKind::Uint && s.width == 4,
+            (FieldType::F32, TypeInner::Scalar(s)) => s.kind == ScalarKind::Float && s.width == 4,
+            (FieldType::Vec2U32, TypeInner::Vector { size: VectorSize::Bi, scalar }) => {
+                scalar.kind == ScalarKind::Uint && scalar.width == 4
+            }
+            (FieldType::Vec2F32, TypeInner::Vector { size: VectorSize::Bi, scalar }) => {
+                scalar.kind == ScalarKind::Float && scalar.width == 4
+            }
+            (FieldType::Vec4F32, TypeInner::Vector { size: VectorSize::Quad, scalar }) => {
+                scalar.kind == ScalarKind::Float && scalar.width == 4
+            }
+            (
+                FieldType::Mat4F32,
+                TypeInner::Matrix {
+                    columns: VectorSize::Quad,
+                    rows: VectorSize::Quad,
+                    scalar,
+                },
+            ) => scalar.kind == ScalarKind::Float && scalar.width == 4,
+            _ => false,
+        }
+    }
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct UniformField {
+    pub name: &'static str,
+    pub offset: u32,
+    pub ty: FieldType,
+}
+
+macro_rules! uniform_field {
+    ($name:ident, $ty:ident) => {
+        UniformField {
+            name: stringify!($name),
+            offset: offset_of!(Uniform, $name) as u32,
+            ty: FieldType::$ty,
+        }
+    };
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UniformLayoutError {
+    MissingUniform { shader: String },
+    OffsetMismatch { shader: String, field: String, expected: u32, found: u32 },
+    TypeMismatch { shader: String, field: String, offset: u32, expected: FieldType, found: String },
+    UnknownField { shader: String, field: String, offset: u32 },
+    TooLarge { shader: String, size: u32, expected: u32 },
+}
+
+impl fmt::Display for UniformLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniformLayoutError::MissingUniform { shader } => {
+                write!(f, "{shader}: no uniform buffer at group 0, binding 0.")
+            }
+            UniformLayoutError::OffsetMismatch { shader, field, expected, found } => write!(
+                f,
+                "{shader}: uniform field `{field}` is at offset {found}, expected offset {expected}."
+            ),
+            UniformLayoutError::TypeMismatch { shader, field, offset, expected, found } => write!(
+                f,
+                "{shader}: uniform field `{field}` at offset {offset} has type {found}, expected {expected}."
+            ),
+            UniformLayoutError::UnknownField { shader, field, offset } => write!(
+                f,
+                "{shader}: uniform field `{field}` at offset {offset} has no counterpart in `Uniform`."
+            ),
+            UniformLayoutError::TooLarge { shader, size, expected } => write!(
+                f,
+                "{shader}: uniform block is {size} bytes, but `Uniform` only has {expected}."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UniformLayoutError {}
+
 impl Uniform {
-    pub fn update_proj(&mut self, res: Vec2) {
-        let aspect_ratio = res.x / res.y;
-        self.proj = Mat4::orthographic_lh(0.0, aspect_ratio, 0.0, 1.0, 0.0, 100.0).to_cols_array_2d();
+    /// Layout every shader uniform block at group 0, binding 0 has to follow.
+    /// Shaders may declare only a prefix of it, the padding is left out.
+    pub const FIELDS: [UniformField; 17] = [
+        uniform_field!(proj, Mat4F32),
+        uniform_field!(res, Vec2U32),
+        uniform_field!(mouse, Vec2F32),
+        uniform_field!(time, U32),
+        uniform_field!(cam_pos, Vec4F32),
+        uniform_field!(cam_dir, Vec4F32),
+        uniform_field!(cam_plane_u, Vec4F32),
+        uniform_field!(cam_plane_v, Vec4F32),
+        uniform_field!(mouse_click, Vec4F32),
+        uniform_field!(date, Vec4F32),
+        uniform_field!(time_delta, F32),
+        uniform_field!(frame, U32),
+        uniform_field!(beat, F32),
+        uniform_field!(bpm, F32),
+        uniform_field!(bass, F32),
+        uniform_field!(mid, F32),
+        uniform_field!(treble, F32),
+    ];
+
+    pub fn update_ray_basis(&mut self, basis: &RayBasis) {
+        self.cam_pos = basis.pos.extend(0.0).to_array();
+        self.cam_dir = basis.dir.extend(0.0).to_array();
+        self.cam_plane_u = basis.plane_u.extend(0.0).to_array();
+        self.cam_plane_v = basis.plane_v.extend(0.0).to_array();
+    }
+
+    /// Stages without any uniform, like the full screen triangle, can't get the layout wrong.
+    pub fn is_used_by(module: &naga::Module) -> bool {
+        module.global_variables.iter().any(|(_, var)| var.space == naga::AddressSpace::Uniform)
+    }
+
+    /// Checks the uniform block of a reflected shader module against `Uniform::FIELDS`.
+    pub fn validate_layout(module: &naga::Module, shader: &str) -> Result<(), UniformLayoutError> {
+        let uniform = module.global_variables.iter().find(|(_, var)| {
+            var.space == naga::AddressSpace::Uniform
+                && var.binding == Some(naga::ResourceBinding { group: 0, binding: 0 })
+        });
+
+        let Some((_, var)) = uniform else {
+            return Err(UniformLayoutError::MissingUniform { shader: shader.to_string() });
+        };
+
+        let naga::TypeInner::Struct { members, span } = &module.types[var.ty].inner else {
+            return Err(UniformLayoutError::MissingUniform { shader: shader.to_string() });
+        };
+
+        for member in members {
+            let name = member.name.clone().unwrap_or_else(|| format!("<unnamed@{}>", member.offset));
+
+            // spir-v may lack member names, fall back to matching by offset
+            let field = match member.name.as_deref() {
+                Some(name) => Self::FIELDS.iter().find(|f| f.name == name),
+                None => Self::FIELDS.iter().find(|f| f.offset == member.offset),
+            };
+
+            let Some(field) = field else {
+                return Err(UniformLayoutError::UnknownField {
+                    shader: shader.to_string(),
+                    field: name,
+                    offset: member.offset,
+                });
+            };
+
+            if field.offset != member.offset {
+                return Err(UniformLayoutError::OffsetMismatch {
+                    shader: shader.to_string(),
+                    field: name,
+                    expected: field.offset,
+                    found: member.offset,
+                });
+            }
+
+            let inner = &module.types[member.ty].inner;
+            if !field.ty.matches(inner) {
+                return Err(UniformLayoutError::TypeMismatch {
+                    shader: shader.to_string(),
+                    field: name,
+                    offset: member.offset,
+                    expected: field.ty,
+                    found: format!("{inner:?}"),
+                });
+            }
+        }
+
+        if *span as usize > size_of::<Uniform>() {
+            return Err(UniformLayoutError::TooLarge {
+                shader: shader.to_string(),
+                size: *span,
+                expected: size_of::<Uniform>() as u32,
+            });
+        }
+
+        Ok(())
     }
 }
 
 impl Default for Uniform {
     fn default() -> Self {
-        Uniform {
+        let mut uniform = Uniform {
             proj: Mat4::IDENTITY.to_cols_array_2d(),
             res: UVec2::ZERO.to_array(),
             mouse: Vec2::ZERO.to_array(),
             time: 0,
             _padding: [0; 3],
-        }
+            cam_pos: [0.0; 4],
+            cam_dir: [0.0; 4],
+            cam_plane_u: [0.0; 4],
+            cam_plane_v: [0.0; 4],
+            mouse_click: [0.0; 4],
+            date: [0.0; 4],
+            time_delta: 0.0,
+            frame: 0,
+            beat: 0.0,
+            bpm: 0.0,
+            bass: 0.0,
+            mid: 0.0,
+            treble: 0.0,
+            _padding_end: [0; 1],
+        };
+        uniform.update_ray_basis(&FlyCamera::default().ray_basis());
+
+        uniform
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_loader::SHADER_FILES;
+
+    fn wgsl(fields: &str) -> naga::Module {
+        let source = format!(
+            "struct Uniform {{ {fields} }};
+            @group(0) @binding(0) var<uniform> ubo: Uniform;
+            @fragment fn main() -> @location(0) vec4<f32> {{ return vec4<f32>(f32(ubo.time)); }}"
+        );
+        naga::front::wgsl::parse_str(&source).unwrap()
+    }
+
+    #[test]
+    fn shipped_shaders_match() {
+        for file in SHADER_FILES {
+            let module = file.compile().unwrap();
+            if Uniform::is_used_by(&module) {
+                assert_eq!(Uniform::validate_layout(&module, file.path), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn prefix_is_accepted() {
+        let module = wgsl("proj: mat4x4<f32>, res: vec2<u32>, mouse: vec2<f32>, time: u32,");
+        assert_eq!(Uniform::validate_layout(&module, "prefix"), Ok(()));
+    }
+
+    #[test]
+    fn reports_moved_field() {
+        let module = wgsl("proj: mat4x4<f32>, cam_pos: vec4<f32>, res: vec2<u32>, time: u32,");
+        assert_eq!(
+            Uniform::validate_layout(&module, "moved"),
+            Err(UniformLayoutError::OffsetMismatch {
+                shader: "moved".to_string(),
+                field: "cam_pos".to_string(),
+                expected: 96,
+                found: 64,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_wrong_type_and_unknown_field() {
+        let module = wgsl("proj: mat4x4<f32>, res: vec2<f32>, mouse: vec2<f32>, time: u32,");
+        assert!(matches!(
+            Uniform::validate_layout(&module, "type"),
+            Err(UniformLayoutError::TypeMismatch { offset: 64, expected: FieldType::Vec2U32, .. })
+        ));
+
+        let module = wgsl("proj: mat4x4<f32>, resolution: vec2<u32>, mouse: vec2<f32>, time: u32,");
+        assert!(matches!(
+            Uniform::validate_layout(&module, "unknown"),
+            Err(UniformLayoutError::UnknownField { offset: 64, .. })
+        ));
+    }
+}
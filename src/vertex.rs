@@ -1,4 +1,4 @@
-use glam::{Vec2, Vec3};
+use glam::{Mat4, Vec2, Vec3};
 use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
 
 #[repr(C)]
@@ -25,6 +25,31 @@ impl Vertex {
     }
 }
 
+/// Per object data, stepped once per instance.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub model: [[f32; 4]; 4],
+}
+
+impl Instance {
+    // the matrix columns follow the vertex attributes
+    pub const ATTRIBUTES: [VertexAttribute; 4] =
+        vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4];
+
+    pub fn new(model: Mat4) -> Self {
+        Self { model: model.to_cols_array_2d() }
+    }
+
+    pub fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
 pub const CUBE_VERTEX_POSITIONS: [Vec3; 24] = [
     // face 0
     Vec3::new(0.0, 0.0, 0.0),
@@ -119,4 +144,21 @@ pub const CUBE_INDICES: [u32; 36] = [
 
     // face 5 - last one needs to be flipped
     23, 21, 20, 22, 21, 23,
-];
\ No newline at end of file
+];
+
+// full screen quad in clip space, used by the octree raymarcher
+pub const QUAD_VERTEX_POSITIONS: [Vec3; 4] = [
+    Vec3::new(-1.0, -1.0, 0.0),
+    Vec3::new(-1.0, 1.0, 0.0),
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(1.0, -1.0, 0.0),
+];
+
+pub const QUAD_UV_COORDS: [Vec2; 4] = [
+    Vec2::new(0.0, 0.0),
+    Vec2::new(0.0, 1.0),
+    Vec2::new(1.0, 1.0),
+    Vec2::new(1.0, 0.0),
+];
+
+pub const QUAD_INDICES: [u32; 6] = [0, 3, 1, 1, 3, 2];
\ No newline at end of file
@@ -0,0 +1,169 @@
+//! Reads MagicaVoxel `.vox` models for `patibu convert`. Only the first model of a file is
+//! used and colors are left to the shader, the palette index becomes the material.
+
+use crate::svo::{Octree, SvoError, MAX_DEPTH};
+use glam::UVec3;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoxError(pub String);
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<SvoError> for VoxError {
+    fn from(e: SvoError) -> Self {
+        VoxError(e.to_string())
+    }
+}
+
+/// One model, positions are in MagicaVoxel's z up coordinates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoxModel {
+    pub size: UVec3,
+    /// position and palette index, 1 to 255
+    pub voxels: Vec<(UVec3, u8)>,
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    let word = bytes.get(at..at + 4)?;
+    Some(u32::from_le_bytes(word.try_into().expect("slices of 4 bytes convert.")))
+}
+
+impl VoxModel {
+    pub fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+        let truncated = || VoxError("the file is truncated.".to_string());
+        if bytes.get(..4) != Some(b"VOX ") {
+            return Err(VoxError("not a MagicaVoxel file.".to_string()));
+        }
+
+        let mut size = None;
+        let mut voxels = None;
+        // skips the version, every chunk is an id, the content size, the children size and the content,
+        // the children of MAIN follow right after its empty content
+        let mut at = 8;
+        while at < bytes.len() {
+            let id = bytes.get(at..at + 4).ok_or_else(truncated)?;
+            let content_size = u32_at(bytes, at + 4).ok_or_else(truncated)? as usize;
+            let content = bytes.get(at + 12..at + 12 + content_size).ok_or_else(truncated)?;
+
+            match id {
+                b"SIZE" if size.is_none() => {
+                    let side = |i: usize| u32_at(content, i * 4).ok_or_else(truncated);
+                    size = Some(UVec3::new(side(0)?, side(1)?, side(2)?));
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let count = u32_at(content, 0).ok_or_else(truncated)? as usize;
+                    let data = content.get(4..4 + count * 4).ok_or_else(truncated)?;
+                    voxels = Some(
+                        data.chunks_exact(4)
+                            .map(|v| (UVec3::new(v[0] as u32, v[1] as u32, v[2] as u32), v[3]))
+                            .filter(|&(_, index)| index != 0)
+                            .collect(),
+                    );
+                }
+                _ => {}
+            }
+            at += 12 + content_size;
+        }
+
+        match (size, voxels) {
+            (Some(size), Some(voxels)) => Ok(Self { size, voxels }),
+            _ => Err(VoxError("the file holds no model.".to_string())),
+        }
+    }
+
+    /// The smallest octree holding the model, y up with the model standing on y = 0.
+    pub fn to_octree(&self) -> Result<Octree, VoxError> {
+        let side = self.size.max_element().max(2);
+        let depth = side.next_power_of_two().trailing_zeros();
+        if depth > MAX_DEPTH {
+            return Err(VoxError(format!("a model of {side} voxels is too large for an octree.")));
+        }
+
+        // turned about x, swapping y and z alone would mirror the model
+        let voxels = self
+            .voxels
+            .iter()
+            .map(|&(pos, index)| match pos.cmplt(self.size).all() {
+                true => Ok((UVec3::new(pos.x, pos.z, self.size.y - 1 - pos.y), index as u32)),
+                false => Err(VoxError(format!("the voxel at {pos} is outside the model of {}.", self.size))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Octree::from_voxels(depth, voxels)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes.extend_from_slice(children);
+        bytes
+    }
+
+    fn vox(size: [u32; 3], voxels: &[[u8; 4]]) -> Vec<u8> {
+        let size: Vec<u8> = size.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+        xyzi.extend(voxels.iter().flatten());
+
+        let mut children = chunk(b"PACK", &1u32.to_le_bytes(), &[]);
+        children.extend(chunk(b"SIZE", &size, &[]));
+        children.extend(chunk(b"XYZI", &xyzi, &[]));
+        children.extend(chunk(b"RGBA", &[0; 1024], &[]));
+
+        let mut file = b"VOX ".to_vec();
+        file.extend_from_slice(&150u32.to_le_bytes());
+        file.extend(chunk(b"MAIN", &[], &children));
+        file
+    }
+
+    #[test]
+    fn reads_first_model() {
+        let model = VoxModel::parse(&vox([3, 2, 5], &[[0, 1, 4, 7], [2, 0, 0, 255]])).unwrap();
+        assert_eq!(model.size, UVec3::new(3, 2, 5));
+        assert_eq!(model.voxels, [(UVec3::new(0, 1, 4), 7), (UVec3::new(2, 0, 0), 255)]);
+
+        // z up becomes y up, y becomes -z
+        let octree = model.to_octree().unwrap();
+        assert_eq!(octree.depth(), 3);
+        assert_eq!(octree.get(UVec3::new(0, 4, 0)), 7);
+        assert_eq!(octree.get(UVec3::new(2, 0, 1)), 255);
+    }
+
+    #[test]
+    fn keeps_handedness() {
+        // a voxel at the origin and one along each axis
+        let model = VoxModel::parse(&vox([2, 2, 2], &[[0, 0, 0, 1], [1, 0, 0, 2], [0, 1, 0, 3], [0, 0, 1, 4]])).unwrap();
+        let octree = model.to_octree().unwrap();
+        let [origin, x, y, z] = [[0, 0, 1], [1, 0, 1], [0, 0, 0], [0, 1, 1]].map(UVec3::from);
+        assert_eq!([origin, x, y, z].map(|pos| octree.get(pos)), [1, 2, 3, 4]);
+
+        // a rotation keeps x cross y pointing along z, a mirror flips it
+        let axis = |pos: UVec3| pos.as_ivec3() - origin.as_ivec3();
+        assert_eq!(axis(x).cross(axis(y)), axis(z));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let error = |bytes: &[u8]| VoxModel::parse(bytes).unwrap_err().0;
+
+        assert!(error(b"PSVO").contains("not a MagicaVoxel"));
+        let file = vox([1, 1, 1], &[[0, 0, 0, 1]]);
+        assert!(error(&file[..file.len() - 10]).contains("truncated"));
+        assert!(error(b"VOX \x96\0\0\0").contains("no model"));
+
+        let outside = VoxModel::parse(&vox([1, 1, 1], &[[0, 1, 0, 1]])).unwrap();
+        assert!(outside.to_octree().unwrap_err().0.contains("outside the model"));
+    }
+}
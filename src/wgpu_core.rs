@@ -1,6 +1,12 @@
+use crate::frame_stats::{FrameStats, HISTORY_FRAMES};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use pollster::block_on;
-use wgpu::{Adapter, Backends, Device, DeviceDescriptor, Features, Instance, InstanceDescriptor, InstanceFlags, Limits, PowerPreference, Queue, RequestAdapterOptions, Surface};
+use log::{debug, error, info, warn};
+use wgpu::{Adapter, AdapterInfo, Backends, Device, DeviceDescriptor, DeviceLostReason, DeviceType, Features, Instance, InstanceDescriptor, InstanceFlags, Limits, PowerPreference, PresentMode, Queue, Surface};
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
@@ -13,19 +19,49 @@ pub struct FrameInfo {
 
     pub fps: f32,
     pub frame_time: f32,
+
+    /// the last `HISTORY_FRAMES` frame times in milliseconds
+    pub history: VecDeque<f32>,
+    /// over `history`, refreshed along with `fps`
+    pub stats: FrameStats,
+    /// every frame time since recording started, for `frame_stats::save_csv`
+    pub recording: Option<Vec<f32>>,
+    resumed: bool,
 }
 
+/// Stands in for the first frame time after the window stopped drawing for a while.
+const RESUMED_FRAME: Duration = Duration::from_millis(16);
+
 impl FrameInfo {
+    /// The next `fetch` doesn't count the time since the last frame, e.g. after low power mode
+    /// idled, so nothing jumps and the statistics don't see a stutter.
+    pub fn resume(&mut self) {
+        self.resumed = true;
+    }
+
     pub fn fetch(&mut self) -> Duration {
         let delta_time = self.last_frame.elapsed();
         let delta_seconds = delta_time.as_secs_f32();
         self.last_frame = Instant::now();
+        if std::mem::take(&mut self.resumed) {
+            return RESUMED_FRAME;
+        }
 
         self.accumulated_time += delta_seconds;
+
+        let milliseconds = delta_seconds * 1000.0;
+        if self.history.len() == HISTORY_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(milliseconds);
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(milliseconds);
+        }
         
         if self.accumulated_time >= 1.0 {
             self.fps = self.frame_count as f32 / self.accumulated_time;
             self.frame_time = (self.accumulated_time * 1000.0) / self.frame_count as f32;
+            self.stats = FrameStats::from_frame_times(self.history.make_contiguous());
             
             self.accumulated_time = 0.0;
             self.frame_count = 0;
@@ -47,43 +83,331 @@ impl Default for FrameInfo {
 
             fps: 0.0,
             frame_time: 0.0,
+
+            history: VecDeque::with_capacity(HISTORY_FRAMES),
+            stats: FrameStats::default(),
+            recording: None,
+            resumed: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdapterError(pub String);
+
+impl fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AdapterError {}
+
+/// An adapter by its index in `patibu devices` or by part of its name, ignoring case, or
+/// `software` for the first one that rasterizes on the cpu.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdapterChoice {
+    Index(usize),
+    Name(String),
+    Software,
+}
+
+impl AdapterChoice {
+    fn matches(&self, index: usize, info: &AdapterInfo) -> bool {
+        match self {
+            AdapterChoice::Index(i) => *i == index,
+            AdapterChoice::Name(name) => info.name.to_lowercase().contains(&name.to_lowercase()),
+            AdapterChoice::Software => info.device_type == DeviceType::Cpu,
+        }
+    }
+}
+
+impl FromStr for AdapterChoice {
+    type Err = AdapterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err(AdapterError("expected an adapter index or name.".to_string())),
+            s if s.eq_ignore_ascii_case("software") => Ok(AdapterChoice::Software),
+            s => Ok(s.parse().map_or_else(|_| AdapterChoice::Name(s.to_string()), AdapterChoice::Index)),
+        }
+    }
+}
+
+impl fmt::Display for AdapterChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdapterChoice::Index(i) => i.fmt(f),
+            AdapterChoice::Name(name) => name.fmt(f),
+            AdapterChoice::Software => f.write_str("software"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdapterSelection {
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    /// `None` picks by `power_preference`
+    pub adapter: Option<AdapterChoice>,
+}
+
+impl Default for AdapterSelection {
+    fn default() -> Self {
+        Self {
+            backends: Backends::VULKAN,
+            power_preference: PowerPreference::HighPerformance,
+            adapter: None,
         }
     }
 }
 
-pub async fn init_wgpu(window: &Window) -> (PhysicalSize<u32>, Instance, Surface, f64, Adapter, Device, Queue) {
+/// `0: llvmpipe (LLVM 15.0.6, 256 bits) (Gl, Cpu)`, indices are what `AdapterChoice::Index` takes.
+pub fn describe_adapter(index: usize, info: &AdapterInfo) -> String {
+    format!("{index}: {} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+fn found(adapters: &[AdapterInfo]) -> String {
+    if adapters.is_empty() {
+        return "found no adapters.".to_string();
+    }
+
+    let lines: Vec<String> = adapters.iter().enumerate().map(|(i, info)| format!("\n  {}", describe_adapter(i, info))).collect();
+    format!("found:{}", lines.concat())
+}
+
+// lower is preferred, software adapters come last
+fn preference(power_preference: PowerPreference, device_type: DeviceType) -> u8 {
+    match (device_type, power_preference) {
+        (DeviceType::IntegratedGpu, PowerPreference::LowPower) => 0,
+        (DeviceType::DiscreteGpu, PowerPreference::LowPower) => 1,
+        (DeviceType::DiscreteGpu, _) => 0,
+        (DeviceType::IntegratedGpu, _) => 1,
+        (DeviceType::VirtualGpu, _) => 2,
+        (DeviceType::Other, _) => 3,
+        (DeviceType::Cpu, _) => 4,
+    }
+}
+
+/// Index into `adapters` of the one to use, `usable` says which can present to the window.
+fn pick(adapters: &[AdapterInfo], usable: &[bool], selection: &AdapterSelection) -> Result<usize, AdapterError> {
+    let Some(choice) = &selection.adapter else {
+        return (0..adapters.len())
+            .filter(|&i| usable[i])
+            .min_by_key(|&i| preference(selection.power_preference, adapters[i].device_type))
+            .ok_or_else(|| AdapterError(format!("no usable adapter, {}", found(adapters))));
+    };
+
+    let matching: Vec<usize> = (0..adapters.len()).filter(|&i| choice.matches(i, &adapters[i])).collect();
+    match matching.iter().find(|&&i| usable[i]) {
+        Some(&i) => Ok(i),
+        None if matching.is_empty() => Err(AdapterError(format!("no adapter matches {choice}, {}", found(adapters)))),
+        None => Err(AdapterError(format!("{} can't present to the window, {}", adapters[matching[0]].name, found(adapters)))),
+    }
+}
+
+/// The adapter `selection` asks for, or the first software adapter of any backend when nothing
+/// else is found and no adapter was asked for by name or index. A window gets its surface from
+/// the instance the adapter belongs to.
+fn open_adapter<'w>(
+    selection: &AdapterSelection,
+    window: Option<&'w Window>,
+) -> Result<(Instance, Option<Surface<'w>>, Adapter), AdapterError> {
+    let open = |backends: Backends| {
+        let instance = Instance::new(InstanceDescriptor {
+            backends,
+            flags: InstanceFlags::empty(),
+            ..Default::default()
+        });
+        let surf = window
+            .map(|window| instance.create_surface(window))
+            .transpose()
+            .map_err(|e| AdapterError(format!("failed to create a surface: {e}")))?;
+
+        let adapters = instance.enumerate_adapters(backends);
+        let infos: Vec<AdapterInfo> = adapters.iter().map(Adapter::get_info).collect();
+        let usable: Vec<bool> = adapters
+            .iter()
+            .map(|adapter| surf.as_ref().is_none_or(|surf| adapter.is_surface_supported(surf)))
+            .collect();
+
+        Ok::<_, AdapterError>((instance, surf, adapters, infos, usable))
+    };
+
+    // the first attempt's surface is gone before the fallback makes another on the same window
+    let error = {
+        let (instance, surf, mut adapters, infos, usable) = open(selection.backends)?;
+        match pick(&infos, &usable, selection) {
+            Ok(i) => return Ok((instance, surf, adapters.swap_remove(i))),
+            Err(e) if selection.adapter.is_some() => return Err(e),
+            Err(e) => e,
+        }
+    };
+
+    warn!("{} trying a software adapter.", error);
+    let (instance, surf, mut adapters, infos, usable) = open(Backends::all())?;
+    let software = (0..adapters.len())
+        .find(|&i| usable[i] && infos[i].device_type == DeviceType::Cpu)
+        .ok_or_else(|| AdapterError(format!("no usable adapter and no software adapter, {}", found(&infos))))?;
+
+    Ok((instance, surf, adapters.swap_remove(software)))
+}
+
+fn log_adapter(adapter: &Adapter) {
+    let info = adapter.get_info();
+    info!("adapter: {} ({:?}, {:?}).", info.name, info.backend, info.device_type);
+    debug!("adapter limits: {:?}.", adapter.limits());
+}
+
+pub async fn init_wgpu<'w>(
+    window: &'w Window,
+    selection: &AdapterSelection,
+) -> Result<(PhysicalSize<u32>, Instance, Surface<'w>, f64, Adapter, Device, Queue), AdapterError> {
     let mut size = window.inner_size();
     size.width = size.width.max(1);
     size.height = size.height.max(1);
 
+    let (instance, surf, adapter) = open_adapter(selection, Some(window))?;
+    let surf = surf.expect("a window always gets a surface.");
+    log_adapter(&adapter);
+
+    let hidpi_factor = window.scale_factor();
+
+    let (dev, queue) = request_device(&adapter).await?;
+
+    Ok((size, instance, surf, hidpi_factor, adapter, dev, queue))
+}
+
+/// Device without a window or surface, for rendering on machines without a display.
+/// The software fallback picks up llvmpipe where there is no gpu.
+pub async fn init_headless(selection: &AdapterSelection) -> Result<(Instance, Adapter, Device, Queue), AdapterError> {
+    let (instance, _, adapter) = open_adapter(selection, None)?;
+    log_adapter(&adapter);
+
+    let (dev, queue) = request_device(&adapter).await?;
+
+    Ok((instance, adapter, dev, queue))
+}
+
+/// Every adapter `backends` offer with their limits, in the order `AdapterChoice::Index` counts.
+pub fn list_adapters(backends: Backends) -> Vec<(AdapterInfo, Limits)> {
     let instance = Instance::new(InstanceDescriptor {
-        backends: Backends::VULKAN,
+        backends,
         flags: InstanceFlags::empty(),
         ..Default::default()
     });
 
-    let surf = instance.create_surface(window).unwrap();
+    instance.enumerate_adapters(backends).iter().map(|adapter| (adapter.get_info(), adapter.limits())).collect()
+}
 
-    let hidpi_factor = window.scale_factor();
+/// `wanted` if the surface supports it, fifo otherwise, which every surface does.
+pub fn choose_present_mode(wanted: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    match wanted {
+        // wgpu resolves these to whatever the surface has
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => wanted,
+        _ if supported.contains(&wanted) => wanted,
+        _ => {
+            warn!("the surface doesn't support {:?}, using Fifo. it supports {:?}.", wanted, supported);
+            PresentMode::Fifo
+        }
+    }
+}
+
+/// Logs errors nobody else handles instead of panicking and returns a flag that is set once the
+/// device is lost. wgpu reports dropping the device as a loss too, see `unwatch_device`.
+pub fn watch_device(dev: &Device) -> Arc<AtomicBool> {
+    dev.on_uncaptured_error(Box::new(|e| error!("{}", e)));
+
+    let lost = Arc::new(AtomicBool::new(false));
+    let flag = lost.clone();
+    dev.set_device_lost_callback(move |reason, message| {
+        if let DeviceLostReason::Unknown | DeviceLostReason::Destroyed = reason {
+            error!("device lost: {}", message);
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+
+    lost
+}
 
-    let adapter = block_on(instance.request_adapter(&RequestAdapterOptions {
-        power_preference: PowerPreference::HighPerformance,
-        compatible_surface: Some(&surf),
-        force_fallback_adapter: false,
-    })).unwrap();
+/// Call before dropping a watched device on purpose, so that isn't logged as a loss.
+pub fn unwatch_device(dev: &Device) {
+    dev.set_device_lost_callback(|_, _| {});
+}
 
-    let (dev, queue) = adapter.request_device(
+/// Also used to replace a lost device, the adapter usually outlives it.
+pub async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), AdapterError> {
+    adapter.request_device(
         &DeviceDescriptor {
             label: None,
-            required_features: Features::SPIRV_SHADER_PASSTHROUGH,
+            required_features: Features::empty(),
             required_limits: Limits {
                 max_storage_buffers_per_shader_stage: 1,
+                max_storage_buffer_binding_size: adapter.limits().max_storage_buffer_binding_size,
 
                 ..Limits::downlevel_webgl2_defaults()
             }.using_resolution(adapter.limits()),
         },
         None,
-    ).await.expect("failed to create device.");
+    ).await.map_err(|e| AdapterError(format!("failed to create a device on {}: {e}", adapter.get_info().name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    (size, instance, surf, hidpi_factor, adapter, dev, queue)
-}
\ No newline at end of file
+    fn adapter(name: &str, device_type: DeviceType) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn picks_by_choice_or_preference() {
+        let adapters = [
+            adapter("llvmpipe", DeviceType::Cpu),
+            adapter("Intel UHD", DeviceType::IntegratedGpu),
+            adapter("NVIDIA RTX", DeviceType::DiscreteGpu),
+        ];
+        let usable = [true; 3];
+        let pick = |power_preference, adapter: Option<&str>| {
+            let adapter = adapter.map(|a| a.parse().unwrap());
+            pick(&adapters, &usable, &AdapterSelection { power_preference, adapter, ..Default::default() })
+        };
+
+        assert_eq!(pick(PowerPreference::HighPerformance, None), Ok(2));
+        assert_eq!(pick(PowerPreference::LowPower, None), Ok(1));
+        assert_eq!(pick(PowerPreference::HighPerformance, Some("0")), Ok(0));
+        assert_eq!(pick(PowerPreference::HighPerformance, Some("intel")), Ok(1));
+        assert_eq!(pick(PowerPreference::HighPerformance, Some("Software")), Ok(0));
+        assert_eq!(
+            pick(PowerPreference::HighPerformance, Some("radeon")).unwrap_err().0,
+            "no adapter matches radeon, found:\n  0: llvmpipe (Vulkan, Cpu)\n  1: Intel UHD (Vulkan, IntegratedGpu)\n  2: NVIDIA RTX (Vulkan, DiscreteGpu)"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_fifo() {
+        let supported = [PresentMode::Fifo, PresentMode::Immediate];
+        assert_eq!(choose_present_mode(PresentMode::Immediate, &supported), PresentMode::Immediate);
+        assert_eq!(choose_present_mode(PresentMode::Mailbox, &supported), PresentMode::Fifo);
+        assert_eq!(choose_present_mode(PresentMode::AutoVsync, &supported), PresentMode::AutoVsync);
+    }
+
+    #[test]
+    fn skips_adapters_without_the_surface() {
+        let adapters = [adapter("NVIDIA RTX", DeviceType::DiscreteGpu), adapter("llvmpipe", DeviceType::Cpu)];
+        let usable = [false, true];
+
+        assert_eq!(pick(&adapters, &usable, &AdapterSelection::default()), Ok(1));
+        let selection = AdapterSelection { adapter: Some(AdapterChoice::Index(0)), ..Default::default() };
+        assert!(pick(&adapters, &usable, &selection).unwrap_err().0.starts_with("NVIDIA RTX can't present"));
+        assert!(pick(&[], &[], &AdapterSelection::default()).unwrap_err().0.ends_with("found no adapters."));
+    }
+}
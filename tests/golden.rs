@@ -0,0 +1,138 @@
+//! Renders both pipelines headless with fixed uniforms and compares them against the
+//! reference images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite the references
+//! after an intended change, mismatches leave the actual and a diff image in `target/golden`.
+//! Everything is drawn on a software adapter over gl, so every machine rasterizes the same way,
+//! the references come from mesa's llvmpipe (LLVM 15.0.6).
+
+use patibu::offscreen::{save_png, Offscreen, OFFSCREEN_FORMAT};
+use patibu::pipelines::PipelineKind;
+use patibu::scene::Scene;
+use patibu::shadertoy::ShadertoyInputs;
+use patibu::wgpu_core::{self, AdapterChoice, AdapterSelection};
+use pollster::block_on;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, UNIX_EPOCH};
+use wgpu::{Adapter, Backends, Device, Instance, Queue};
+use winit::dpi::PhysicalSize;
+
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(320, 180);
+const TIME: u32 = 1500;
+// per channel, software rasterizers round slightly differently between versions
+const TOLERANCE: u8 = 3;
+// pixels allowed past the tolerance, edges may land on either side of a pixel center
+const MAX_OUTLIERS: usize = 32;
+
+fn gpu() -> &'static (Instance, Adapter, Device, Queue) {
+    static GPU: OnceLock<(Instance, Adapter, Device, Queue)> = OnceLock::new();
+    GPU.get_or_init(|| {
+        let selection = AdapterSelection {
+            backends: Backends::GL,
+            adapter: Some(AdapterChoice::Software),
+            ..Default::default()
+        };
+        block_on(wgpu_core::init_headless(&selection))
+            .unwrap_or_else(|e| panic!("the golden images need a software gl adapter such as llvmpipe: {e}"))
+    })
+}
+
+fn render(kind: PipelineKind) -> Vec<u8> {
+    let (_, _, dev, queue) = gpu();
+
+    let camera = Scene::default_camera(kind);
+    let (mut scene, mut uniform) = Scene::new(dev, kind, SIZE, OFFSCREEN_FORMAT, camera.projection()).unwrap();
+    let offscreen = Offscreen::new(dev, SIZE);
+
+    uniform.time = TIME;
+    camera.write_uniform(&mut uniform, SIZE.width as f32 / SIZE.height as f32);
+    let date = UNIX_EPOCH + Duration::from_millis(TIME as u64);
+    ShadertoyInputs::default().write_uniform(&mut uniform, 0.0, date);
+    queue.write_buffer(&scene.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+    offscreen.render_scene(dev, queue, &mut scene)
+}
+
+fn load_png(path: &Path) -> (PhysicalSize<u32>, Vec<u8>) {
+    let decoder = png::Decoder::new(File::open(path).unwrap_or_else(|e| {
+        panic!("missing reference {}: {e}, run with UPDATE_GOLDEN=1 to create it.", path.display())
+    }));
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{} is not rgba8.", path.display());
+    pixels.truncate(info.buffer_size());
+
+    (PhysicalSize::new(info.width, info.height), pixels)
+}
+
+// mismatches in red over a dimmed copy of the reference
+fn diff_image(expected: &[u8], actual: &[u8]) -> (Vec<u8>, usize) {
+    let mut outliers = 0;
+    let diff = expected
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .flat_map(|(e, a)| {
+            let off = e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > TOLERANCE);
+            outliers += off as usize;
+            match off {
+                true => [255, 0, 0, 255],
+                false => [e[0] / 4, e[1] / 4, e[2] / 4, 255],
+            }
+        })
+        .collect();
+
+    (diff, outliers)
+}
+
+fn assert_matches_golden(name: &str, actual: &[u8]) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference = root.join("tests/golden").join(format!("{name}.png"));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        save_png(&reference, SIZE, actual).unwrap();
+        return;
+    }
+
+    let (size, expected) = load_png(&reference);
+    assert_eq!(size, SIZE, "{name}: reference has a different size.");
+
+    let (diff, outliers) = diff_image(&expected, actual);
+    if outliers > MAX_OUTLIERS {
+        let out_dir = root.join("target/golden");
+        fs::create_dir_all(&out_dir).unwrap();
+        save_png(&out_dir.join(format!("{name}_actual.png")), SIZE, actual).unwrap();
+        save_png(&out_dir.join(format!("{name}_diff.png")), SIZE, &diff).unwrap();
+
+        panic!(
+            "{name}: {outliers} pixels differ by more than {TOLERANCE}, see {}.",
+            out_dir.display()
+        );
+    }
+}
+
+#[test]
+fn cube_matches_golden() {
+    assert_matches_golden("cube", &render(PipelineKind::Mesh));
+}
+
+#[test]
+fn octree_matches_golden() {
+    assert_matches_golden("octree", &render(PipelineKind::Octree));
+}
+
+#[test]
+fn shadertoy_matches_golden() {
+    assert_matches_golden("shadertoy", &render(PipelineKind::Shadertoy));
+}
+
+#[test]
+fn diff_counts_outliers() {
+    let expected = [100, 100, 100, 255, 0, 0, 0, 255];
+    let actual = [100 + TOLERANCE, 100, 100, 255, 0, 0, TOLERANCE + 1, 255];
+
+    let (diff, outliers) = diff_image(&expected, &actual);
+    assert_eq!(outliers, 1);
+    assert_eq!(&diff[4..], &[255, 0, 0, 255]);
+}